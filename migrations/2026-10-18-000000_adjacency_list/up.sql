@@ -0,0 +1,16 @@
+-- A legacy table linking each row to its parent by id, for converting to ltree.
+-- There is no foreign key on parent_id, so rows can be orphaned.
+CREATE TABLE categories (
+       id SERIAL PRIMARY KEY,
+       parent_id INTEGER,
+       slug TEXT,
+       path ltree
+);
+
+INSERT INTO categories (id, parent_id, slug) VALUES
+       (1, NULL, 'animals'),
+       (2, 1, 'birds'),
+       (3, 1, 'fish'),
+       (4, 2, 'owls'),
+       (5, NULL, 'plants'),
+       (6, 5, 'trees');
@@ -0,0 +1,580 @@
+//! Converting adjacency-list tables, whose rows point at their parent through
+//! a `parent_id` column, to ltree paths.
+//!
+//! [`dry_run`] computes every row's path with a recursive CTE and returns the
+//! paths without touching the table, and [`fill`] runs the same CTE as an
+//! `UPDATE` that writes them into an ltree column. Both refuse to proceed if
+//! any row is part of a cycle, hangs below a missing parent or is given a
+//! `NULL` label, reporting the first such row as an [`AdjacencyError`].
+//!
+//! Each row's label comes from a [`LabelStrategy`]: its id ([`IdLabels`]),
+//! another of its columns such as a slug ([`ColumnLabels`]), or a closure
+//! called with its id ([`CustomLabels`]). Labels must be valid ltree labels,
+//! or Postgres rejects them when building the paths.
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use diesel::pg::Pg;
+use diesel::query_builder::{AstPass, Query, QueryFragment, QueryId};
+use diesel::query_dsl::LoadQuery;
+use diesel::result::QueryResult;
+use diesel::serialize::ToSql;
+use diesel::sql_types::{Array, HasSqlType, Nullable};
+use diesel::{Column, Connection, RunQueryDsl, Table};
+
+use crate::dsl::LtreeOrNullableLtree;
+use crate::sql_types;
+use crate::values::Ltree;
+
+use self::query::{IdsQuery, PathsQuery, UpdateQuery};
+
+/// Why [`dry_run`] or [`fill`] could not compute a path for every row.
+#[derive(Debug, PartialEq)]
+pub enum AdjacencyError<I> {
+    /// The rows with these ids, each the parent of the next and the last the
+    /// parent of the first, form a cycle.
+    Cycle {
+        ids: Vec<I>,
+    },
+    /// The row's parent id does not match any row.
+    Orphan {
+        id: I,
+        parent: I,
+    },
+    /// The label strategy gave the row a `NULL` label.
+    NullLabel {
+        id: I,
+    },
+    Database(diesel::result::Error),
+}
+
+impl<I: fmt::Debug> fmt::Display for AdjacencyError<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdjacencyError::Cycle { ids } => write!(f, "rows {:?} form a cycle", ids),
+            AdjacencyError::Orphan { id, parent } => {
+                write!(f, "row {:?} has missing parent {:?}", id, parent)
+            }
+            AdjacencyError::NullLabel { id } => write!(f, "row {:?} has a NULL label", id),
+            AdjacencyError::Database(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<I: fmt::Debug> Error for AdjacencyError<I> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AdjacencyError::Database(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<I> From<diesel::result::Error> for AdjacencyError<I> {
+    fn from(e: diesel::result::Error) -> Self {
+        AdjacencyError::Database(e)
+    }
+}
+
+/// How [`dry_run`] and [`fill`] label each row, where `ST` is the SQL type of
+/// the id column and `I` its Rust type.
+pub trait LabelStrategy<ST, I> {
+    /// Whether [`set_ids`](LabelStrategy::set_ids) must be called with every
+    /// id before the strategy is rendered.
+    #[doc(hidden)]
+    const NEEDS_IDS: bool = false;
+
+    #[doc(hidden)]
+    fn set_ids(&mut self, _ids: Vec<I>) {}
+
+    /// Writes the label expression, evaluated for each row of the table, which
+    /// is aliased as `ltree_rows`.
+    #[doc(hidden)]
+    fn walk_label<'b>(&'b self, id: &'static str, out: &mut AstPass<'_, 'b, Pg>)
+        -> QueryResult<()>;
+
+    /// Writes anything the label expression needs joined to the table.
+    #[doc(hidden)]
+    fn walk_join<'b>(
+        &'b self,
+        _id: &'static str,
+        _out: &mut AstPass<'_, 'b, Pg>,
+    ) -> QueryResult<()> {
+        Ok(())
+    }
+}
+
+/// Labels each row with its id, as text.
+#[derive(Debug, Clone, Copy)]
+pub struct IdLabels;
+
+impl<ST, I> LabelStrategy<ST, I> for IdLabels {
+    fn walk_label<'b>(
+        &'b self,
+        id: &'static str,
+        out: &mut AstPass<'_, 'b, Pg>,
+    ) -> QueryResult<()> {
+        out.push_sql("ltree_rows.");
+        out.push_identifier(id)?;
+        out.push_sql("::text");
+        Ok(())
+    }
+}
+
+/// Labels each row with another of its columns, such as a slug, as text.
+#[derive(Debug, Clone, Copy)]
+pub struct ColumnLabels<C>(pub C);
+
+impl<C: Column, ST, I> LabelStrategy<ST, I> for ColumnLabels<C> {
+    fn walk_label<'b>(
+        &'b self,
+        _id: &'static str,
+        out: &mut AstPass<'_, 'b, Pg>,
+    ) -> QueryResult<()> {
+        out.push_sql("ltree_rows.");
+        out.push_identifier(C::NAME)?;
+        out.push_sql("::text");
+        Ok(())
+    }
+}
+
+/// Labels each row with the result of a closure called with its id.
+///
+/// The ids are loaded first and sent back with their labels as arrays, which
+/// the CTE joins to the table.
+pub struct CustomLabels<F, I> {
+    label: F,
+    ids: Vec<I>,
+    labels: Vec<String>,
+}
+
+impl<F, I> CustomLabels<F, I>
+where
+    F: FnMut(&I) -> String,
+{
+    pub fn new(label: F) -> Self {
+        CustomLabels {
+            label,
+            ids: Vec::new(),
+            labels: Vec::new(),
+        }
+    }
+}
+
+impl<F, I: fmt::Debug> fmt::Debug for CustomLabels<F, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomLabels")
+            .field("ids", &self.ids)
+            .field("labels", &self.labels)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, ST, I> LabelStrategy<ST, I> for CustomLabels<F, I>
+where
+    F: FnMut(&I) -> String,
+    ST: 'static,
+    Pg: HasSqlType<ST>,
+    I: ToSql<ST, Pg>,
+{
+    const NEEDS_IDS: bool = true;
+
+    fn set_ids(&mut self, ids: Vec<I>) {
+        self.labels = ids.iter().map(&mut self.label).collect();
+        self.ids = ids;
+    }
+
+    fn walk_label<'b>(
+        &'b self,
+        _id: &'static str,
+        out: &mut AstPass<'_, 'b, Pg>,
+    ) -> QueryResult<()> {
+        out.push_sql("ltree_custom.label");
+        Ok(())
+    }
+
+    fn walk_join<'b>(&'b self, id: &'static str, out: &mut AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql(" JOIN unnest(");
+        out.push_bind_param::<Array<ST>, Vec<I>>(&self.ids)?;
+        out.push_sql(", ");
+        out.push_bind_param::<Array<diesel::sql_types::Text>, Vec<String>>(&self.labels)?;
+        out.push_sql(") AS ltree_custom (id, label) ON ltree_rows.");
+        out.push_identifier(id)?;
+        out.push_sql(" = ltree_custom.id");
+        Ok(())
+    }
+}
+
+pub(crate) mod query {
+    use super::*;
+
+    // The `WITH RECURSIVE` clause shared by the queries below. `ltree_paths`
+    // holds the path of each row reachable from a row with no parent; rows in
+    // a cycle or below a missing parent never appear in it.
+    pub struct Cte<'a, F, ST, I, L> {
+        pub(super) from: F,
+        pub(super) id: &'static str,
+        pub(super) parent: &'static str,
+        pub(super) labels: &'a L,
+        pub(super) types: PhantomData<fn() -> (ST, I)>,
+    }
+
+    impl<F, ST, I, L> QueryFragment<Pg> for Cte<'_, F, ST, I, L>
+    where
+        F: QueryFragment<Pg>,
+        L: LabelStrategy<ST, I>,
+    {
+        fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+            out.push_sql("WITH RECURSIVE ltree_labels (id, parent, label) AS (SELECT ltree_rows.");
+            out.push_identifier(self.id)?;
+            out.push_sql(", ltree_rows.");
+            out.push_identifier(self.parent)?;
+            out.push_sql(", ");
+            self.labels.walk_label(self.id, &mut out)?;
+            out.push_sql(" FROM ");
+            self.from.walk_ast(out.reborrow())?;
+            out.push_sql(" AS ltree_rows");
+            self.labels.walk_join(self.id, &mut out)?;
+            out.push_sql(
+                "), ltree_paths (id, path) AS (\
+                 SELECT id, text2ltree(label) FROM ltree_labels WHERE parent IS NULL \
+                 UNION ALL \
+                 SELECT ltree_labels.id, ltree_paths.path || text2ltree(ltree_labels.label) \
+                 FROM ltree_labels JOIN ltree_paths ON ltree_labels.parent = ltree_paths.id) ",
+            );
+            Ok(())
+        }
+    }
+
+    // `... SELECT id, parent, path`, with a `NULL` path for each row the CTE
+    // could not reach. Those rows sort last, by id.
+    pub struct PathsQuery<'a, F, ST, I, L>(pub(super) Cte<'a, F, ST, I, L>);
+
+    impl<F, ST, I, L> Query for PathsQuery<'_, F, ST, I, L> {
+        type SqlType = (ST, Nullable<ST>, Nullable<sql_types::Ltree>);
+    }
+
+    impl<F, ST, I, L> QueryFragment<Pg> for PathsQuery<'_, F, ST, I, L>
+    where
+        F: QueryFragment<Pg>,
+        L: LabelStrategy<ST, I>,
+    {
+        fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+            self.0.walk_ast(out.reborrow())?;
+            out.push_sql(
+                "SELECT ltree_labels.id, ltree_labels.parent, ltree_paths.path \
+                 FROM ltree_labels LEFT JOIN ltree_paths ON ltree_labels.id = ltree_paths.id \
+                 ORDER BY ltree_paths.path, ltree_labels.id",
+            );
+            Ok(())
+        }
+    }
+
+    // `... UPDATE table SET path = ltree_paths.path FROM ltree_paths WHERE ...`
+    pub struct UpdateQuery<'a, F, ST, I, L> {
+        pub(super) cte: Cte<'a, F, ST, I, L>,
+        pub(super) path: &'static str,
+    }
+
+    impl<F, ST, I, L> QueryFragment<Pg> for UpdateQuery<'_, F, ST, I, L>
+    where
+        F: QueryFragment<Pg>,
+        L: LabelStrategy<ST, I>,
+    {
+        fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+            self.cte.walk_ast(out.reborrow())?;
+            out.push_sql("UPDATE ");
+            self.cte.from.walk_ast(out.reborrow())?;
+            out.push_sql(" SET ");
+            out.push_identifier(self.path)?;
+            out.push_sql(" = ltree_paths.path FROM ltree_paths WHERE ");
+            self.cte.from.walk_ast(out.reborrow())?;
+            out.push_sql(".");
+            out.push_identifier(self.cte.id)?;
+            out.push_sql(" = ltree_paths.id");
+            Ok(())
+        }
+    }
+
+    // `SELECT id FROM table`, for labelling the ids in Rust.
+    pub struct IdsQuery<F, ST> {
+        pub(super) from: F,
+        pub(super) id: &'static str,
+        pub(super) id_type: PhantomData<fn() -> ST>,
+    }
+
+    impl<F, ST> Query for IdsQuery<F, ST> {
+        type SqlType = ST;
+    }
+
+    impl<F: QueryFragment<Pg>, ST> QueryFragment<Pg> for IdsQuery<F, ST> {
+        fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+            out.push_sql("SELECT ");
+            out.push_identifier(self.id)?;
+            out.push_sql(" FROM ");
+            self.from.walk_ast(out.reborrow())
+        }
+    }
+
+    // The CTE's text depends on the column names and label strategy, so none
+    // of these can be cached by type.
+    macro_rules! no_static_query_id {
+        ($($ty:ty),*) => {$(
+            impl<'a, F, ST, I, L> QueryId for $ty {
+                type QueryId = ();
+                const HAS_STATIC_QUERY_ID: bool = false;
+            }
+
+            impl<'a, F, ST, I, L, Conn> RunQueryDsl<Conn> for $ty {}
+        )*};
+    }
+
+    no_static_query_id!(PathsQuery<'a, F, ST, I, L>, UpdateQuery<'a, F, ST, I, L>);
+
+    impl<F, ST> QueryId for IdsQuery<F, ST> {
+        type QueryId = ();
+        const HAS_STATIC_QUERY_ID: bool = false;
+    }
+
+    impl<F, ST, Conn> RunQueryDsl<Conn> for IdsQuery<F, ST> {}
+}
+
+pub(crate) type Row<I> = (I, Option<I>, Option<Ltree>);
+
+// Turns the rows of a `PathsQuery` into paths, or explains why the first row
+// without one was not reached.
+pub(crate) fn check_paths<I>(rows: Vec<Row<I>>) -> Result<Vec<(I, Ltree)>, AdjacencyError<I>>
+where
+    I: Eq + Hash + Clone,
+{
+    let reached = rows.iter().take_while(|row| row.2.is_some()).count();
+    if reached == rows.len() {
+        return Ok(rows
+            .into_iter()
+            .map(|(id, _, path)| (id, path.expect("all rows were reached")))
+            .collect());
+    }
+
+    let with_path = rows[..reached]
+        .iter()
+        .map(|row| &row.0)
+        .collect::<HashSet<_>>();
+    let without_path = rows[reached..]
+        .iter()
+        .map(|row| (&row.0, row.1.as_ref()))
+        .collect::<HashMap<_, _>>();
+    let mut chain = vec![&rows[reached].0];
+    loop {
+        let id = chain[chain.len() - 1];
+        match without_path[id] {
+            None => return Err(AdjacencyError::NullLabel { id: id.clone() }),
+            Some(parent) if with_path.contains(parent) => {
+                return Err(AdjacencyError::NullLabel { id: id.clone() })
+            }
+            Some(parent) if !without_path.contains_key(parent) => {
+                return Err(AdjacencyError::Orphan {
+                    id: id.clone(),
+                    parent: parent.clone(),
+                })
+            }
+            Some(parent) => {
+                if let Some(start) = chain.iter().position(|&seen| seen == parent) {
+                    let mut ids = chain[start..]
+                        .iter()
+                        .map(|&id| id.clone())
+                        .collect::<Vec<_>>();
+                    // The chain runs from child to parent.
+                    ids.reverse();
+                    return Err(AdjacencyError::Cycle { ids });
+                }
+                chain.push(parent);
+            }
+        }
+    }
+}
+
+/// Computes the path of every row of `table` from its `parent` column,
+/// without changing the table, returning `(id, path)` pairs ordered by path.
+///
+/// `parent` is `NULL` for the roots of the tree.
+///
+/// ```no_run
+/// # use diesel::prelude::*;
+/// # use diesel_ltree::adjacency::{self, ColumnLabels};
+/// # diesel::table! { use diesel_ltree::sql_types::Ltree; use diesel::sql_types::*;
+/// #     categories (id) { id -> Int4, parent_id -> Nullable<Int4>, slug -> Text, path -> Nullable<Ltree>, } }
+/// # fn run(conn: &mut PgConnection) -> Result<(), diesel_ltree::adjacency::AdjacencyError<i32>> {
+/// let paths: Vec<(i32, diesel_ltree::Ltree)> = adjacency::dry_run(
+///     conn,
+///     categories::table,
+///     categories::id,
+///     categories::parent_id,
+///     ColumnLabels(categories::slug),
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn dry_run<Conn, T, Id, P, L, I>(
+    conn: &mut Conn,
+    table: T,
+    id: Id,
+    parent: P,
+    mut labels: L,
+) -> Result<Vec<(I, Ltree)>, AdjacencyError<I>>
+where
+    Conn: Connection<Backend = Pg>,
+    T: Table,
+    T::FromClause: QueryFragment<Pg>,
+    Id: Column<Table = T>,
+    P: Column<Table = T, SqlType = Nullable<Id::SqlType>>,
+    L: LabelStrategy<Id::SqlType, I>,
+    I: Eq + Hash + Clone,
+    IdsQuery<T::FromClause, Id::SqlType>: LoadQuery<'static, Conn, I>,
+    for<'a> PathsQuery<'a, T::FromClause, Id::SqlType, I, L>: LoadQuery<'a, Conn, Row<I>>,
+{
+    prepare_labels(conn, &table, &id, &mut labels)?;
+    check_paths(paths_query(&table, &id, &parent, &labels).load(conn)?)
+}
+
+/// Fills the ltree column `path` of every row of `table` from its `parent`
+/// column, returning the number of rows updated.
+///
+/// This checks every row can be given a path before updating any, in a
+/// transaction (a savepoint, if the caller already has one open).
+///
+/// ```no_run
+/// # use diesel::prelude::*;
+/// # use diesel_ltree::adjacency::{self, IdLabels};
+/// # diesel::table! { use diesel_ltree::sql_types::Ltree; use diesel::sql_types::*;
+/// #     categories (id) { id -> Int4, parent_id -> Nullable<Int4>, path -> Nullable<Ltree>, } }
+/// # fn run(conn: &mut PgConnection) -> Result<(), diesel_ltree::adjacency::AdjacencyError<i32>> {
+/// let filled = adjacency::fill(
+///     conn,
+///     categories::table,
+///     categories::id,
+///     categories::parent_id,
+///     IdLabels,
+///     categories::path,
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn fill<Conn, T, Id, P, L, I, Path>(
+    conn: &mut Conn,
+    table: T,
+    id: Id,
+    parent: P,
+    mut labels: L,
+    path: Path,
+) -> Result<usize, AdjacencyError<I>>
+where
+    Conn: Connection<Backend = Pg>,
+    T: Table,
+    T::FromClause: QueryFragment<Pg>,
+    Id: Column<Table = T>,
+    P: Column<Table = T, SqlType = Nullable<Id::SqlType>>,
+    L: LabelStrategy<Id::SqlType, I>,
+    I: Eq + Hash + Clone,
+    Path: Column<Table = T>,
+    Path::SqlType: LtreeOrNullableLtree,
+    IdsQuery<T::FromClause, Id::SqlType>: LoadQuery<'static, Conn, I>,
+    for<'a> PathsQuery<'a, T::FromClause, Id::SqlType, I, L>: LoadQuery<'a, Conn, Row<I>>,
+{
+    conn.transaction(|conn| {
+        prepare_labels(conn, &table, &id, &mut labels)?;
+        check_paths(paths_query(&table, &id, &parent, &labels).load(conn)?)?;
+        Ok(update_query(&table, &id, &parent, &labels, &path).execute(conn)?)
+    })
+}
+
+// The builders below are shared with the async `dry_run` and `fill`.
+
+pub(crate) fn ids_query<T, Id>(table: &T, id: &Id) -> IdsQuery<T::FromClause, Id::SqlType>
+where
+    T: Table,
+    Id: Column<Table = T>,
+{
+    IdsQuery {
+        from: table.from_clause(),
+        id: column_name(id),
+        id_type: PhantomData,
+    }
+}
+
+pub(crate) fn paths_query<'a, T, Id, P, I, L>(
+    table: &T,
+    id: &Id,
+    parent: &P,
+    labels: &'a L,
+) -> PathsQuery<'a, T::FromClause, Id::SqlType, I, L>
+where
+    T: Table,
+    Id: Column<Table = T>,
+    P: Column<Table = T>,
+{
+    PathsQuery(cte(table, id, parent, labels))
+}
+
+pub(crate) fn update_query<'a, T, Id, P, I, L, Path>(
+    table: &T,
+    id: &Id,
+    parent: &P,
+    labels: &'a L,
+    path: &Path,
+) -> UpdateQuery<'a, T::FromClause, Id::SqlType, I, L>
+where
+    T: Table,
+    Id: Column<Table = T>,
+    P: Column<Table = T>,
+    Path: Column<Table = T>,
+{
+    UpdateQuery {
+        cte: cte(table, id, parent, labels),
+        path: column_name(path),
+    }
+}
+
+fn column_name<C: Column>(_: &C) -> &'static str {
+    C::NAME
+}
+
+fn cte<'a, T, Id, P, I, L>(
+    table: &T,
+    id: &Id,
+    parent: &P,
+    labels: &'a L,
+) -> query::Cte<'a, T::FromClause, Id::SqlType, I, L>
+where
+    T: Table,
+    Id: Column<Table = T>,
+    P: Column<Table = T>,
+{
+    query::Cte {
+        from: table.from_clause(),
+        id: column_name(id),
+        parent: column_name(parent),
+        labels,
+        types: PhantomData,
+    }
+}
+
+fn prepare_labels<Conn, T, Id, L, I>(
+    conn: &mut Conn,
+    table: &T,
+    id: &Id,
+    labels: &mut L,
+) -> QueryResult<()>
+where
+    T: Table,
+    Id: Column<Table = T>,
+    L: LabelStrategy<Id::SqlType, I>,
+    IdsQuery<T::FromClause, Id::SqlType>: LoadQuery<'static, Conn, I>,
+{
+    if L::NEEDS_IDS {
+        labels.set_ids(ids_query(table, id).load(conn)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! Async counterparts of the [`subtree`](crate::subtree) and
+//! [`adjacency`](crate::adjacency) helpers, for
+//! [`diesel-async`](https://docs.rs/diesel-async) connections.
+//!
+//! The expression methods and functions in [`dsl`](crate::dsl) and
+//! [`functions`](crate::functions) build ordinary Diesel queries, so they need
+//! nothing extra: run them with `diesel_async::RunQueryDsl` instead of
+//! `diesel::RunQueryDsl`.
+//!
+//! ```no_run
+//! # use diesel::prelude::*;
+//! # use diesel_async::{AsyncPgConnection, RunQueryDsl};
+//! # use diesel_ltree::{Ltree, LtreeExtensions};
+//! # diesel::table! { use diesel_ltree::sql_types::Ltree; use diesel::sql_types::*;
+//! #     my_tree (id) { id -> Int4, path -> Ltree, } }
+//! # async fn run(conn: &mut AsyncPgConnection) -> QueryResult<()> {
+//! let below: Vec<i32> = my_tree::table
+//!     .select(my_tree::id)
+//!     .filter(my_tree::path.contained_by(Ltree("root.eukaryota".to_string())))
+//!     .load(conn)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+pub mod adjacency;
+pub mod subtree;
@@ -0,0 +1,122 @@
+//! Async versions of [`dry_run`](crate::adjacency::dry_run) and
+//! [`fill`](crate::adjacency::fill), which run the same queries.
+use std::hash::Hash;
+
+use diesel::pg::Pg;
+use diesel::query_builder::QueryFragment;
+use diesel::result::QueryResult;
+use diesel::sql_types::Nullable;
+use diesel::{Column, Table};
+use diesel_async::methods::LoadQuery;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+
+use crate::adjacency::query::{IdsQuery, PathsQuery};
+use crate::adjacency::{
+    check_paths, ids_query, paths_query, update_query, AdjacencyError, LabelStrategy, Row,
+};
+use crate::dsl::LtreeOrNullableLtree;
+use crate::values::Ltree;
+
+/// Computes the path of every row of `table` from its `parent` column,
+/// without changing the table, as [`crate::adjacency::dry_run`] does.
+///
+/// ```no_run
+/// # use diesel::prelude::*;
+/// # use diesel_async::AsyncPgConnection;
+/// # use diesel_ltree::adjacency::{AdjacencyError, ColumnLabels};
+/// # diesel::table! { use diesel_ltree::sql_types::Ltree; use diesel::sql_types::*;
+/// #     categories (id) { id -> Int4, parent_id -> Nullable<Int4>, slug -> Text, path -> Nullable<Ltree>, } }
+/// # async fn run(conn: &mut AsyncPgConnection) -> Result<(), AdjacencyError<i32>> {
+/// let paths: Vec<(i32, diesel_ltree::Ltree)> = diesel_ltree::asynchronous::adjacency::dry_run(
+///     conn,
+///     categories::table,
+///     categories::id,
+///     categories::parent_id,
+///     ColumnLabels(categories::slug),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn dry_run<Conn, T, Id, P, L, I>(
+    conn: &mut Conn,
+    table: T,
+    id: Id,
+    parent: P,
+    mut labels: L,
+) -> Result<Vec<(I, Ltree)>, AdjacencyError<I>>
+where
+    Conn: AsyncConnection<Backend = Pg> + Send,
+    T: Table + 'static,
+    T::FromClause: QueryFragment<Pg>,
+    Id: Column<Table = T> + 'static,
+    P: Column<Table = T, SqlType = Nullable<Id::SqlType>>,
+    L: LabelStrategy<Id::SqlType, I>,
+    I: Eq + Hash + Clone + Send,
+    IdsQuery<T::FromClause, Id::SqlType>: LoadQuery<'static, Conn, I>,
+    for<'a> PathsQuery<'a, T::FromClause, Id::SqlType, I, L>: LoadQuery<'a, Conn, Row<I>>,
+{
+    prepare_labels(conn, ids_query(&table, &id), &mut labels).await?;
+    let rows = paths_query(&table, &id, &parent, &labels)
+        .load(conn)
+        .await?;
+    check_paths(rows)
+}
+
+/// Fills the ltree column `path` of every row of `table` from its `parent`
+/// column, in a transaction, as [`crate::adjacency::fill`] does.
+pub async fn fill<Conn, T, Id, P, L, I, Path>(
+    conn: &mut Conn,
+    table: T,
+    id: Id,
+    parent: P,
+    mut labels: L,
+    path: Path,
+) -> Result<usize, AdjacencyError<I>>
+where
+    Conn: AsyncConnection<Backend = Pg> + Send,
+    T: Table + Send + 'static,
+    T::FromClause: QueryFragment<Pg> + Send,
+    Id: Column<Table = T> + Send + 'static,
+    P: Column<Table = T, SqlType = Nullable<Id::SqlType>> + Send,
+    L: LabelStrategy<Id::SqlType, I> + Send + Sync,
+    I: Eq + Hash + Clone + Send,
+    Path: Column<Table = T> + Send,
+    Path::SqlType: LtreeOrNullableLtree,
+    IdsQuery<T::FromClause, Id::SqlType>: LoadQuery<'static, Conn, I>,
+    for<'a> PathsQuery<'a, T::FromClause, Id::SqlType, I, L>: LoadQuery<'a, Conn, Row<I>>,
+{
+    conn.transaction(move |conn| {
+        async move {
+            prepare_labels(conn, ids_query(&table, &id), &mut labels).await?;
+            let rows = paths_query(&table, &id, &parent, &labels)
+                .load(conn)
+                .await?;
+            check_paths(rows)?;
+            let update = update_query(&table, &id, &parent, &labels, &path);
+            Ok(update.execute(conn).await?)
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+// Takes the ids query rather than the table and id column so that `fill`'s
+// future needs neither to be `Sync`.
+async fn prepare_labels<Conn, F: 'static, ST: 'static, L, I>(
+    conn: &mut Conn,
+    ids: IdsQuery<F, ST>,
+    labels: &mut L,
+) -> QueryResult<()>
+where
+    Conn: AsyncConnection<Backend = Pg> + Send,
+    L: LabelStrategy<ST, I>,
+    I: Send,
+    IdsQuery<F, ST>: LoadQuery<'static, Conn, I>,
+{
+    if L::NEEDS_IDS {
+        labels.set_ids(ids.load(conn).await?);
+    }
+    Ok(())
+}
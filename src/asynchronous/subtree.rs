@@ -0,0 +1,153 @@
+//! Async versions of [`move_subtree`](crate::subtree::move_subtree),
+//! [`copy_subtree`](crate::subtree::copy_subtree) and
+//! [`delete_subtree`](crate::subtree::delete_subtree), which run the same
+//! statements.
+use diesel::expression::Expression;
+use diesel::pg::Pg;
+use diesel::query_builder::{
+    AsChangeset, AsQuery, DeleteStatement, IntoUpdateTarget, Query, QueryFragment, UpdateStatement,
+};
+use diesel::query_dsl::methods::{FilterDsl, SelectDsl};
+use diesel::result::OptionalExtension;
+use diesel::{Column, Table};
+use diesel_async::methods::ExecuteDsl;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::{AsyncConnection, RunQueryDsl};
+
+use crate::sql_types;
+use crate::subtree::{
+    copy_statement, delete_all_statement, delete_statement, move_statement, reparent_clash,
+    reparent_statement, DeleteFilter, DeleteMode, InsertColumns, MoveFilter, MoveSet, MovedPath,
+    ReparentSet, SubtreeError,
+};
+use crate::values::{Ltree, MAX_LEVELS};
+
+/// Moves the node at `from`, along with all of its descendants, so that it
+/// sits at `to`, as [`crate::subtree::move_subtree`] does.
+///
+/// ```no_run
+/// # use diesel::prelude::*;
+/// # use diesel_async::AsyncPgConnection;
+/// # use diesel_ltree::Ltree;
+/// # diesel::table! { use diesel_ltree::sql_types::Ltree; use diesel::sql_types::*;
+/// #     taxonomy (id) { id -> Int4, path -> Ltree, } }
+/// # async fn run(conn: &mut AsyncPgConnection) -> Result<(), diesel_ltree::subtree::SubtreeError> {
+/// let moved = diesel_ltree::asynchronous::subtree::move_subtree(
+///     conn,
+///     taxonomy::table,
+///     taxonomy::path,
+///     &Ltree("root.eukaryota.plantae".to_string()),
+///     &Ltree("root.archaea.plantae".to_string()),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn move_subtree<Conn, T, C, F, W, S>(
+    conn: &mut Conn,
+    table: T,
+    column: C,
+    from: &Ltree,
+    to: &Ltree,
+) -> Result<usize, SubtreeError>
+where
+    Conn: AsyncConnection<Backend = Pg> + Send,
+    T: FilterDsl<MoveFilter<C>, Output = F>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    F: IntoUpdateTarget<WhereClause = W>,
+    MoveSet<C>: AsChangeset<Target = F::Table, Changeset = S>,
+    UpdateStatement<F::Table, W, S>: AsQuery + ExecuteDsl<Conn>,
+{
+    let statement = move_statement(table, column, from, to)?;
+    Ok(statement.execute(conn).await?)
+}
+
+/// Copies the node at `from`, along with all of its descendants, so that the
+/// copy sits at `to`, as [`crate::subtree::copy_subtree`] does.
+#[allow(clippy::too_many_arguments)]
+pub async fn copy_subtree<Conn, T, C, Cols, R, Sel, F, Q>(
+    conn: &mut Conn,
+    table: T,
+    column: C,
+    from: &Ltree,
+    to: &Ltree,
+    _columns: Cols,
+    remap: R,
+) -> Result<usize, SubtreeError>
+where
+    Conn: AsyncConnection<Backend = Pg> + Send,
+    T: Table + Copy + FilterDsl<MoveFilter<C>, Output = F>,
+    T::FromClause: QueryFragment<Pg> + Send,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    Cols: InsertColumns<Table = T> + Send,
+    R: FnOnce(MovedPath<C>) -> Sel,
+    Sel: Expression,
+    F: SelectDsl<Sel, Output = Q>,
+    Q: Query<SqlType = Cols::SqlType> + QueryFragment<Pg> + Send,
+{
+    let statement = copy_statement::<_, _, Cols, _, _, _, _>(table, column, from, to, remap);
+    Ok(statement.execute(conn).await?)
+}
+
+/// Deletes the subtree at `root` as chosen by `mode`, as
+/// [`crate::subtree::delete_subtree`] does.
+pub async fn delete_subtree<Conn, T, C, F, W, S, G, V>(
+    conn: &mut Conn,
+    table: T,
+    column: C,
+    root: &Ltree,
+    mode: DeleteMode,
+) -> Result<usize, SubtreeError>
+where
+    Conn: AsyncConnection<Backend = Pg> + Send,
+    T: Table
+        + FilterDsl<DeleteFilter<C>, Output = F>
+        + FilterDsl<MoveFilter<C>, Output = G>
+        + Copy
+        + Send,
+    T::FromClause: QueryFragment<Pg> + Send,
+    C: Column<SqlType = sql_types::Ltree> + Copy + Send,
+    F: IntoUpdateTarget<WhereClause = W>,
+    G: IntoUpdateTarget<WhereClause = V>,
+    DeleteStatement<F::Table, W>: ExecuteDsl<Conn>,
+    DeleteStatement<G::Table, V>: ExecuteDsl<Conn>,
+    ReparentSet<C>: AsChangeset<Target = F::Table, Changeset = S>,
+    UpdateStatement<F::Table, W, S>: AsQuery + ExecuteDsl<Conn>,
+{
+    let depth = root.nlevel();
+    match mode {
+        DeleteMode::Subtree => {
+            let statement = delete_all_statement(table, column, root);
+            Ok(statement.execute(conn).await?)
+        }
+        DeleteMode::KeepRoot => {
+            let statement = delete_statement(table, column, root, depth + 1, MAX_LEVELS);
+            Ok(statement.execute(conn).await?)
+        }
+        DeleteMode::ReparentChildren => {
+            conn.transaction(move |conn| {
+                async move {
+                    let deleted = delete_statement(table, column, root, depth, depth)
+                        .execute(conn)
+                        .await?;
+                    if deleted == 0 {
+                        return Err(SubtreeError::MissingRoot { root: root.clone() });
+                    }
+                    let taken = reparent_clash::<_, C>(table, root)
+                        .get_result::<Ltree>(conn)
+                        .await
+                        .optional()?;
+                    if let Some(path) = taken {
+                        return Err(SubtreeError::PathTaken { path });
+                    }
+                    reparent_statement(table, column, root)
+                        .execute(conn)
+                        .await?;
+                    Ok(deleted)
+                }
+                .scope_boxed()
+            })
+            .await
+        }
+    }
+}
@@ -2,6 +2,24 @@
 //! [ltree](https://www.postgresql.org/docs/current/ltree.html) extension, 
 //! including all of the operations and functions for working with hierarchial 
 //! data in Postgres.
+//!
+//! # Cargo features
+//!
+//! - `serde`: `Serialize` and `Deserialize` for the value types, and
+//!   `Serialize` for [`tree::Forest`].
+//! - `postgres-types`: `ToSql` and `FromSql` from the `postgres-types` crate
+//!   for [`Ltree`], [`Lquery`] and [`Ltxtquery`], for use with `postgres` and
+//!   `tokio-postgres`.
+//! - `sqlx`: `Type`, `Encode` and `Decode` for sqlx's Postgres driver for the
+//!   same types, which also bind as arrays.
+//! - `diesel-async`: async versions of the [`subtree`] and [`adjacency`]
+//!   helpers in the `asynchronous` module, for `diesel-async` connections.
+//! - `sqlite`: SQLite support for local development and tests, storing the
+//!   types as TEXT and emulating the common functions and operators. See the
+//!   `sqlite` module.
+//! - `hyphen-labels`: accept `-` in labels when parsing, as servers running
+//!   PostgreSQL 16 or later do. Without it, parsing follows earlier servers
+//!   and rejects `-`.
 extern crate byteorder;
 #[macro_use]
 extern crate diesel;
@@ -9,60 +27,444 @@ extern crate diesel;
 #[cfg(test)]
 mod tests;
 
+pub mod adjacency;
+#[cfg(feature = "diesel-async")]
+pub mod asynchronous;
+pub mod lquery;
+pub mod ltxtquery;
+pub mod schema;
+pub mod subtree;
+pub mod tree;
+
+#[cfg(feature = "postgres-types")]
+mod postgres_types;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(feature = "sqlx")]
+mod sqlx_types;
+
 pub mod sql_types {
     use diesel::query_builder::QueryId;
     use diesel::sql_types::SqlType;
 
     #[derive(SqlType, Clone, Copy, QueryId)]
     #[diesel(postgres_type(name = "ltree"))]
+    #[cfg_attr(feature = "sqlite", diesel(sqlite_type(name = "Text")))]
     pub struct Ltree;
 
     #[derive(SqlType, Clone, Copy, QueryId)]
     #[diesel(postgres_type(name = "lquery"))]
+    #[cfg_attr(feature = "sqlite", diesel(sqlite_type(name = "Text")))]
     pub struct Lquery;
 
     #[derive(SqlType, Clone, Copy, QueryId)]
     #[diesel(postgres_type(name = "ltxtquery"))]
+    #[cfg_attr(feature = "sqlite", diesel(sqlite_type(name = "Text")))]
     pub struct Ltxtquery;
 }
 
 pub mod values {
-    use std::io::{Read, Write};
+    use std::cmp::Ordering;
+    use std::fmt;
+    use std::io::Write;
+    use std::str::FromStr;
 
-    use byteorder::{ReadBytesExt, WriteBytesExt};
+    use byteorder::WriteBytesExt;
     use diesel::deserialize::{self, FromSqlRow};
     use diesel::expression::AsExpression;
     use diesel::pg::{Pg, PgValue};
     use diesel::sql_types::Text;
 
+    mod encoding;
+    mod labels;
+
+    pub use self::encoding::{DecodeError, Encoding};
+    pub use self::labels::{Ancestors, Label, Labels, Prefixes};
+
     /// A ltree [label path](https://www.postgresql.org/docs/current/ltree.html#LTREE-DEFINITIONS).
-    #[derive(Debug, PartialEq, Eq, Clone, FromSqlRow, AsExpression)]
+    ///
+    /// Paths are ordered the same way as `ORDER BY` orders an ltree column:
+    /// label by label, with a path sorting before its descendants.
+    #[derive(Debug, PartialEq, Eq, Hash, Clone, FromSqlRow, AsExpression)]
     #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[cfg_attr(feature = "serde", serde(transparent))]
     #[diesel(sql_type = crate::sql_types::Ltree)]
     pub struct Ltree(pub String);
 
+    /// The maximum number of characters allowed in a single label.
+    pub const MAX_LABEL_LENGTH: usize = 1000;
+
+    /// The maximum number of labels allowed in a single path.
+    pub const MAX_LEVELS: usize = u16::MAX as usize;
+
+    /// Why a string could not be parsed as an [`Ltree`].
+    ///
+    /// Every variant carries the byte offset into the input at which the
+    /// problem was found.
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub enum LtreeParseError {
+        /// A character that is not allowed in a label.
+        InvalidCharacter { offset: usize, character: char },
+        /// A label with no characters, e.g. `a..b` or a trailing dot.
+        EmptyLabel { offset: usize },
+        /// A label longer than [`MAX_LABEL_LENGTH`] characters.
+        LabelTooLong { offset: usize, length: usize },
+        /// A path with more than [`MAX_LEVELS`] labels.
+        TooManyLevels { offset: usize },
+    }
+
+    impl LtreeParseError {
+        /// The byte offset into the input at which the error was found.
+        pub fn offset(&self) -> usize {
+            match *self {
+                LtreeParseError::InvalidCharacter { offset, .. }
+                | LtreeParseError::EmptyLabel { offset }
+                | LtreeParseError::LabelTooLong { offset, .. }
+                | LtreeParseError::TooManyLevels { offset } => offset,
+            }
+        }
+    }
+
+    impl fmt::Display for LtreeParseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match *self {
+                LtreeParseError::InvalidCharacter { offset, character } => write!(
+                    f,
+                    "invalid character {:?} in ltree label at byte {}",
+                    character, offset
+                ),
+                LtreeParseError::EmptyLabel { offset } => {
+                    write!(f, "empty ltree label at byte {}", offset)
+                }
+                LtreeParseError::LabelTooLong { offset, length } => write!(
+                    f,
+                    "ltree label at byte {} is {} characters long, the maximum is {}",
+                    offset, length, MAX_LABEL_LENGTH
+                ),
+                LtreeParseError::TooManyLevels { offset } => write!(
+                    f,
+                    "ltree has more than {} labels, starting at byte {}",
+                    MAX_LEVELS, offset
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for LtreeParseError {}
+
+    /// Checks whether `c` may appear in a label.
+    ///
+    /// This is `A-Za-z0-9` and `_`, the set PostgreSQL 15 and earlier accept
+    /// in a database with the C locale. PostgreSQL 16 also accepts `-`, which
+    /// is allowed only with the `hyphen-labels` feature. Under other locales
+    /// the server may accept non-ASCII letters too, which this rejects.
+    pub(crate) fn is_label_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_' || (cfg!(feature = "hyphen-labels") && c == '-')
+    }
+
+    impl Ltree {
+        /// Parses and validates a label path, applying the same rules as the
+        /// ltree extension's input function.
+        ///
+        /// Labels may contain `A-Za-z0-9` and `_`, as on PostgreSQL 15 and
+        /// earlier with the C locale, and also `-` with the `hyphen-labels`
+        /// feature, as on PostgreSQL 16. The empty string is a valid path with
+        /// zero labels.
+        pub fn parse(s: &str) -> Result<Ltree, LtreeParseError> {
+            validate(s)?;
+            Ok(Ltree(s.to_string()))
+        }
+    }
+
+    fn validate(s: &str) -> Result<(), LtreeParseError> {
+        if s.is_empty() {
+            return Ok(());
+        }
+
+        let mut start = 0;
+        for (level, label) in s.split('.').enumerate() {
+            if level == MAX_LEVELS {
+                return Err(LtreeParseError::TooManyLevels { offset: start });
+            }
+            if label.is_empty() {
+                return Err(LtreeParseError::EmptyLabel { offset: start });
+            }
+            if let Some((i, character)) = label.char_indices().find(|&(_, c)| !is_label_char(c)) {
+                return Err(LtreeParseError::InvalidCharacter {
+                    offset: start + i,
+                    character,
+                });
+            }
+            if label.len() > MAX_LABEL_LENGTH {
+                return Err(LtreeParseError::LabelTooLong {
+                    offset: start,
+                    length: label.len(),
+                });
+            }
+            start += label.len() + 1;
+        }
+
+        Ok(())
+    }
+
+    impl FromStr for Ltree {
+        type Err = LtreeParseError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            Ltree::parse(s)
+        }
+    }
+
+    impl TryFrom<&str> for Ltree {
+        type Error = LtreeParseError;
+
+        fn try_from(s: &str) -> Result<Self, Self::Error> {
+            Ltree::parse(s)
+        }
+    }
+
+    impl TryFrom<String> for Ltree {
+        type Error = LtreeParseError;
+
+        fn try_from(s: String) -> Result<Self, Self::Error> {
+            validate(&s)?;
+            Ok(Ltree(s))
+        }
+    }
+
+    impl Ord for Ltree {
+        fn cmp(&self, other: &Self) -> Ordering {
+            // Labels compare bytewise, and a shorter label or path sorts first.
+            self.labels()
+                .map(|label| label.as_str().as_bytes())
+                .cmp(other.labels().map(|label| label.as_str().as_bytes()))
+        }
+    }
+
+    impl PartialOrd for Ltree {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    /// Client-side equivalents of the ltree functions and operators. These
+    /// produce the same results as their SQL counterparts in
+    /// [`functions`](crate::functions) and [`dsl`](crate::dsl), returning
+    /// `None` where Postgres would raise an error or return `NULL`.
+    impl Ltree {
+        fn split(&self) -> Vec<&str> {
+            self.labels().map(|label| label.as_str()).collect()
+        }
+
+        fn from_labels(labels: &[&str]) -> Ltree {
+            Ltree(labels.join("."))
+        }
+
+        /// Returns the number of labels in the path, like `nlevel`.
+        pub fn nlevel(&self) -> usize {
+            self.labels().count()
+        }
+
+        /// Returns the labels from position `start` up to, but not including,
+        /// position `end`, like `subltree`.
+        pub fn subltree(&self, start: i32, end: i32) -> Option<Ltree> {
+            let labels = self.split();
+            let (start, end) = (i64::from(start), i64::from(end));
+            let levels = labels.len() as i64;
+            if start < 0 || end < 0 || start >= levels || start > end {
+                return None;
+            }
+            Some(Ltree::from_labels(
+                &labels[start as usize..end.min(levels) as usize],
+            ))
+        }
+
+        /// Returns `len` labels starting at position `offset`, like the three
+        /// argument form of `subpath`.
+        ///
+        /// A negative `offset` counts from the end of the path, and a negative
+        /// `len` leaves that many labels off the end of the path.
+        pub fn subpath(&self, offset: i32, len: i32) -> Option<Ltree> {
+            let levels = self.nlevel() as i64;
+            let len = i64::from(len);
+            let mut start = i64::from(offset);
+            // Postgres applies the negative offset adjustment twice.
+            if start < 0 {
+                start += levels;
+            }
+            if start < 0 {
+                start += levels;
+            }
+            let end = match len {
+                len if len < 0 => levels + len,
+                _ => start + len,
+            };
+            let clamp = |n: i64| n.clamp(i64::from(i32::MIN), i64::from(i32::MAX)) as i32;
+            self.subltree(clamp(start), clamp(end))
+        }
+
+        /// Returns the position of the first occurrence of `other` in the path
+        /// at or after position `offset`, like `index`.
+        ///
+        /// A negative `offset` counts from the end of the path.
+        pub fn index_of(&self, other: &Ltree, offset: i32) -> Option<usize> {
+            let haystack = self.split();
+            let needle = other.split();
+            let start = match offset {
+                offset if offset >= 0 => offset as usize,
+                offset if offset.unsigned_abs() as usize >= haystack.len() => 0,
+                offset => haystack.len() - offset.unsigned_abs() as usize,
+            };
+            if needle.is_empty() || haystack.len() < needle.len() + start {
+                return None;
+            }
+            (start..=haystack.len() - needle.len())
+                .find(|&i| haystack[i..i + needle.len()] == needle[..])
+        }
+
+        /// Appends `other` to the path, like the `||` operator.
+        ///
+        /// Returns `None` if the result would have more than [`MAX_LEVELS`]
+        /// labels.
+        pub fn concat(&self, other: &Ltree) -> Option<Ltree> {
+            if self.nlevel() + other.nlevel() > MAX_LEVELS {
+                return None;
+            }
+            Some(match (self.0.is_empty(), other.0.is_empty()) {
+                (true, _) => other.clone(),
+                (_, true) => self.clone(),
+                _ => Ltree(format!("{}.{}", self.0, other.0)),
+            })
+        }
+
+        /// Returns the path with its last label removed, or `None` if the path
+        /// has fewer than two labels.
+        pub fn parent(&self) -> Option<Ltree> {
+            self.0.rfind('.').map(|i| Ltree(self.0[..i].to_string()))
+        }
+
+        /// Returns the longest common ancestor of `paths`, like `lca`.
+        ///
+        /// As in Postgres, the result never includes the last label of any of
+        /// the inputs, and is `None` if `paths` is empty or contains an empty
+        /// path.
+        pub fn lca(paths: &[Ltree]) -> Option<Ltree> {
+            let (first, rest) = paths.split_first()?;
+            let first = first.split();
+            if first.is_empty() {
+                return None;
+            }
+            let mut common = first.len() - 1;
+            for path in rest {
+                let labels = path.split();
+                if labels.is_empty() {
+                    return None;
+                }
+                common = first
+                    .iter()
+                    .zip(&labels[..labels.len() - 1])
+                    .take(common)
+                    .take_while(|(a, b)| a == b)
+                    .count();
+            }
+            Some(Ltree::from_labels(&first[..common]))
+        }
+
+        /// Checks whether this path is an ancestor of, or equal to, `other`,
+        /// like the `@>` operator.
+        pub fn is_ancestor_of(&self, other: &Ltree) -> bool {
+            let ancestor = self.split();
+            let descendant = other.split();
+            descendant.starts_with(&ancestor)
+        }
+
+        /// Checks whether this path is a descendant of, or equal to, `other`,
+        /// like the `<@` operator.
+        pub fn is_descendant_of(&self, other: &Ltree) -> bool {
+            other.is_ancestor_of(self)
+        }
+
+        /// Returns the first of `candidates` that is an ancestor of, or equal
+        /// to, this path, like the `?@>` operator.
+        pub fn first_ancestor_in<'a>(&self, candidates: &'a [Ltree]) -> Option<&'a Ltree> {
+            candidates.iter().find(|c| c.is_ancestor_of(self))
+        }
+
+        /// Returns the first of `candidates` that is a descendant of, or equal
+        /// to, this path, like the `?<@` operator.
+        pub fn first_descendant_in<'a>(&self, candidates: &'a [Ltree]) -> Option<&'a Ltree> {
+            candidates.iter().find(|c| c.is_descendant_of(self))
+        }
+    }
+
+    /// The only version of the binary protocol used by the ltree extension
+    /// for ltree, lquery and ltxtquery.
+    const BINARY_VERSION: i8 = 1;
+
+    /// Why a binary ltree, lquery or ltxtquery value could not be decoded.
+    #[derive(Debug, PartialEq, Eq, Clone)]
+    pub enum WireFormatError {
+        /// The value was empty, so did not have a version byte.
+        Truncated,
+        /// The version byte was not one this crate understands.
+        UnsupportedVersion(i8),
+        /// The text after the version byte was not valid UTF-8. The offset is
+        /// that of the first invalid byte within the text.
+        InvalidUtf8 { offset: usize },
+    }
+
+    impl fmt::Display for WireFormatError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match *self {
+                WireFormatError::Truncated => f.write_str("binary ltree value is empty"),
+                WireFormatError::UnsupportedVersion(version) => write!(
+                    f,
+                    "unsupported ltree binary protocol version {}, expected {}",
+                    version, BINARY_VERSION
+                ),
+                WireFormatError::InvalidUtf8 { offset } => write!(
+                    f,
+                    "binary ltree value contains invalid UTF-8 at byte {}",
+                    offset
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for WireFormatError {}
+
+    /// Writes `text` in the binary format shared by ltree, lquery and
+    /// ltxtquery: a version byte followed by the text form.
+    pub(crate) fn encode_binary<W: Write>(text: &str, out: &mut W) -> std::io::Result<()> {
+        out.write_i8(BINARY_VERSION)?;
+        out.write_all(text.as_bytes())
+    }
+
+    /// Reads the text form out of a binary ltree, lquery or ltxtquery value.
+    pub(crate) fn decode_binary(bytes: &[u8]) -> Result<&str, WireFormatError> {
+        let (&version, text) = bytes.split_first().ok_or(WireFormatError::Truncated)?;
+        if version as i8 != BINARY_VERSION {
+            return Err(WireFormatError::UnsupportedVersion(version as i8));
+        }
+        std::str::from_utf8(text).map_err(|e| WireFormatError::InvalidUtf8 {
+            offset: e.valid_up_to(),
+        })
+    }
+
     impl diesel::serialize::ToSql<crate::sql_types::Ltree, Pg> for Ltree {
         fn to_sql<'b>(
             &'b self,
             out: &mut diesel::serialize::Output<'b, '_, Pg>,
         ) -> diesel::serialize::Result {
-            out.write_i8(1)?;
-            out.write_all(self.0.as_bytes())?;
+            encode_binary(&self.0, out)?;
             Ok(diesel::serialize::IsNull::No)
         }
     }
 
     impl diesel::deserialize::FromSql<crate::sql_types::Ltree, Pg> for Ltree {
         fn from_sql(value: PgValue) -> deserialize::Result<Self> {
-            let mut raw = value.as_bytes();
-
-            let version = raw.read_i8()?;
-            debug_assert_eq!(version, 1, "Unknown ltree binary protocol version.");
-
-            let mut buf = String::new();
-            raw.read_to_string(&mut buf)?;
-            Ok(Ltree(buf))
+            let text = decode_binary(value.as_bytes())?;
+            Ok(Ltree(text.to_string()))
         }
     }
 
@@ -92,6 +494,120 @@ pub mod values {
             String::from_sql(bytes).map(Ltree)
         }
     }
+
+    /// A [lquery](https://www.postgresql.org/docs/current/ltree.html#LTREE-DEFINITIONS) pattern for matching label paths.
+    #[derive(Debug, PartialEq, Eq, Clone, FromSqlRow, AsExpression)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
+    #[diesel(sql_type = crate::sql_types::Lquery)]
+    pub struct Lquery(pub String);
+
+    impl fmt::Display for Lquery {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    impl diesel::serialize::ToSql<crate::sql_types::Lquery, Pg> for Lquery {
+        fn to_sql<'b>(
+            &'b self,
+            out: &mut diesel::serialize::Output<'b, '_, Pg>,
+        ) -> diesel::serialize::Result {
+            encode_binary(&self.0, out)?;
+            Ok(diesel::serialize::IsNull::No)
+        }
+    }
+
+    impl diesel::deserialize::FromSql<crate::sql_types::Lquery, Pg> for Lquery {
+        fn from_sql(value: PgValue) -> deserialize::Result<Self> {
+            let text = decode_binary(value.as_bytes())?;
+            Ok(Lquery(text.to_string()))
+        }
+    }
+
+    impl<DB> diesel::serialize::ToSql<Text, DB> for Lquery
+    where
+        String: diesel::serialize::ToSql<Text, DB>,
+        DB: diesel::backend::Backend,
+        DB: diesel::sql_types::HasSqlType<crate::sql_types::Lquery>,
+    {
+        fn to_sql<'b>(
+            &'b self,
+            out: &mut diesel::serialize::Output<'b, '_, DB>,
+        ) -> diesel::serialize::Result {
+            self.0.to_sql(out)
+        }
+    }
+
+    impl<DB> diesel::deserialize::FromSql<Text, DB> for Lquery
+    where
+        String: diesel::deserialize::FromSql<Text, DB>,
+        DB: diesel::backend::Backend,
+        DB: diesel::sql_types::HasSqlType<crate::sql_types::Lquery>,
+    {
+        fn from_sql(
+            bytes: <DB as diesel::backend::Backend>::RawValue<'_>,
+        ) -> deserialize::Result<Self> {
+            String::from_sql(bytes).map(Lquery)
+        }
+    }
+
+    /// A [ltxtquery](https://www.postgresql.org/docs/current/ltree.html#LTREE-DEFINITIONS) full-text-search-like pattern for matching label paths.
+    #[derive(Debug, PartialEq, Eq, Clone, FromSqlRow, AsExpression)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
+    #[diesel(sql_type = crate::sql_types::Ltxtquery)]
+    pub struct Ltxtquery(pub String);
+
+    impl fmt::Display for Ltxtquery {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    impl diesel::serialize::ToSql<crate::sql_types::Ltxtquery, Pg> for Ltxtquery {
+        fn to_sql<'b>(
+            &'b self,
+            out: &mut diesel::serialize::Output<'b, '_, Pg>,
+        ) -> diesel::serialize::Result {
+            encode_binary(&self.0, out)?;
+            Ok(diesel::serialize::IsNull::No)
+        }
+    }
+
+    impl diesel::deserialize::FromSql<crate::sql_types::Ltxtquery, Pg> for Ltxtquery {
+        fn from_sql(value: PgValue) -> deserialize::Result<Self> {
+            let text = decode_binary(value.as_bytes())?;
+            Ok(Ltxtquery(text.to_string()))
+        }
+    }
+
+    impl<DB> diesel::serialize::ToSql<Text, DB> for Ltxtquery
+    where
+        String: diesel::serialize::ToSql<Text, DB>,
+        DB: diesel::backend::Backend,
+        DB: diesel::sql_types::HasSqlType<crate::sql_types::Ltxtquery>,
+    {
+        fn to_sql<'b>(
+            &'b self,
+            out: &mut diesel::serialize::Output<'b, '_, DB>,
+        ) -> diesel::serialize::Result {
+            self.0.to_sql(out)
+        }
+    }
+
+    impl<DB> diesel::deserialize::FromSql<Text, DB> for Ltxtquery
+    where
+        String: diesel::deserialize::FromSql<Text, DB>,
+        DB: diesel::backend::Backend,
+        DB: diesel::sql_types::HasSqlType<crate::sql_types::Ltxtquery>,
+    {
+        fn from_sql(
+            bytes: <DB as diesel::backend::Backend>::RawValue<'_>,
+        ) -> deserialize::Result<Self> {
+            String::from_sql(bytes).map(Ltxtquery)
+        }
+    }
 }
 
 pub mod functions {
@@ -100,14 +616,81 @@ pub mod functions {
 
     define_sql_function!(fn subltree(ltree: Ltree, start: Int4, end: Int4) -> Ltree);
     define_sql_function!(fn subpath(ltree: Ltree, offset: Int4, len: Int4) -> Ltree);
-    // define_sql_function!(fn subpath(ltree: Ltree, offset: Int4) -> Ltree);
+    define_sql_function! {
+        /// The two argument form of `subpath`, running from `offset` to the end of the path.
+        #[sql_name = "subpath"]
+        fn subpath_from(ltree: Ltree, offset: Int4) -> Ltree;
+    }
     define_sql_function!(fn nlevel(ltree: Ltree) -> Int4);
-    //define_sql_function!(fn index(a: Ltree, b: Ltree) -> Int4);
+    define_sql_function! {
+        /// The two argument form of `index`, searching from the start of the path.
+        #[sql_name = "index"]
+        fn index_of(a: Ltree, b: Ltree) -> Int4;
+    }
     define_sql_function!(fn index(a: Ltree, b: Ltree, offset: Int4) -> Int4);
     define_sql_function!(fn text2ltree(text: Text) -> Ltree);
     define_sql_function!(fn ltree2text(ltree: Ltree) -> Text);
+    define_sql_function! {
+        /// `text2ltree` for nullable text, returning `NULL` for `NULL`.
+        #[sql_name = "text2ltree"]
+        fn text2ltree_nullable(text: Nullable<Text>) -> Nullable<Ltree>;
+    }
+    define_sql_function! {
+        /// `ltree2text` for a nullable ltree, returning `NULL` for `NULL`.
+        #[sql_name = "ltree2text"]
+        fn ltree2text_nullable(ltree: Nullable<Ltree>) -> Nullable<Text>;
+    }
     define_sql_function!(fn lca(ltrees: Array<Ltree>) -> Ltree);
 
+    // The explicit argument forms of `lca`, named after their arity.
+    define_sql_function! {
+        #[sql_name = "lca"]
+        fn lca2(a: Ltree, b: Ltree) -> Ltree;
+    }
+    define_sql_function! {
+        #[sql_name = "lca"]
+        fn lca3(a: Ltree, b: Ltree, c: Ltree) -> Ltree;
+    }
+    define_sql_function! {
+        #[sql_name = "lca"]
+        fn lca4(a: Ltree, b: Ltree, c: Ltree, d: Ltree) -> Ltree;
+    }
+    define_sql_function! {
+        #[sql_name = "lca"]
+        fn lca5(a: Ltree, b: Ltree, c: Ltree, d: Ltree, e: Ltree) -> Ltree;
+    }
+    define_sql_function! {
+        #[sql_name = "lca"]
+        fn lca6(a: Ltree, b: Ltree, c: Ltree, d: Ltree, e: Ltree, f: Ltree) -> Ltree;
+    }
+    define_sql_function! {
+        #[sql_name = "lca"]
+        fn lca7(a: Ltree, b: Ltree, c: Ltree, d: Ltree, e: Ltree, f: Ltree, g: Ltree) -> Ltree;
+    }
+    define_sql_function! {
+        #[sql_name = "lca"]
+        #[allow(clippy::too_many_arguments)]
+        fn lca8(
+            a: Ltree,
+            b: Ltree,
+            c: Ltree,
+            d: Ltree,
+            e: Ltree,
+            f: Ltree,
+            g: Ltree,
+            h: Ltree,
+        ) -> Ltree;
+    }
+
+    define_sql_function! {
+        /// Requires PostgreSQL 16 or later (ltree 1.3).
+        fn hash_ltree(ltree: Ltree) -> Int4;
+    }
+    define_sql_function! {
+        /// Requires PostgreSQL 16 or later (ltree 1.3).
+        fn hash_ltree_extended(ltree: Ltree, seed: Int8) -> Int8;
+    }
+
     define_sql_function!(fn lquery(x: Text) -> Lquery);
     define_sql_function!(fn ltxtquery(x: Text) -> Ltxtquery);
 }
@@ -115,11 +698,19 @@ pub mod functions {
 pub mod dsl {
     use crate::sql_types::*;
     use diesel::expression::{AsExpression, Expression};
-    use diesel::sql_types::Array;
+    use diesel::sql_types::is_nullable::{IsNullable, MaybeNullable, NotNull};
+    use diesel::sql_types::{Array, MaybeNullableType, Nullable, SqlType, Text};
 
-    mod predicates {
+    pub(crate) mod predicates {
+        use super::{LtxtqueryArrayOrNullableLtxtqueryArray, SameNullability};
         use crate::sql_types::*;
+        use diesel::expression::{
+            AppearsOnTable, Expression, SelectableExpression, TypedExpressionType, ValidGrouping,
+        };
         use diesel::pg::Pg;
+        use diesel::query_builder::{AstPass, QueryFragment, QueryId};
+        use diesel::result::QueryResult;
+        use diesel::sql_types::Nullable;
 
         diesel::infix_operator!(Contains, " @> ", backend: Pg);
         diesel::infix_operator!(ContainedBy, " <@ ", backend: Pg);
@@ -127,23 +718,245 @@ pub mod dsl {
         diesel::infix_operator!(MatchesAny, " ? ", backend: Pg);
         diesel::infix_operator!(TMatches, " @ ", backend: Pg);
         diesel::infix_operator!(Concat, " || ", Ltree, backend: Pg);
-        diesel::infix_operator!(FirstContains, " ?@> ", Ltree, backend: Pg);
-        diesel::infix_operator!(FirstContainedBy, " ?<@ ", Ltree, backend: Pg);
-        diesel::infix_operator!(FirstMatches, " ?~ ", Ltree, backend: Pg);
-        diesel::infix_operator!(FirstTMatches, " ?@ ", Ltree, backend: Pg);
+
+        /// `ANY(array)` over an array of ltxtquery, which has no operators of its own.
+        #[derive(Debug, Clone, Copy, QueryId, ValidGrouping)]
+        pub struct AnyLtxtquery<E>(E);
+
+        impl<E> AnyLtxtquery<E> {
+            pub fn new(array: E) -> Self {
+                AnyLtxtquery(array)
+            }
+        }
+
+        impl<E> Expression for AnyLtxtquery<E>
+        where
+            E: Expression,
+            E::SqlType: LtxtqueryArrayOrNullableLtxtqueryArray,
+            SameNullability<E, Ltxtquery>: TypedExpressionType,
+        {
+            type SqlType = SameNullability<E, Ltxtquery>;
+        }
+
+        impl<E: QueryFragment<Pg>> QueryFragment<Pg> for AnyLtxtquery<E> {
+            fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+                out.push_sql("ANY(");
+                self.0.walk_ast(out.reborrow())?;
+                out.push_sql(")");
+                Ok(())
+            }
+        }
+
+        impl<E, QS> AppearsOnTable<QS> for AnyLtxtquery<E>
+        where
+            E: AppearsOnTable<QS>,
+            Self: Expression,
+        {
+        }
+
+        impl<E, QS> SelectableExpression<QS> for AnyLtxtquery<E>
+        where
+            E: SelectableExpression<QS>,
+            Self: AppearsOnTable<QS>,
+        {
+        }
+
+        // Postgres returns NULL from these when no element matches, whatever
+        // the nullability of their arguments.
+        macro_rules! first_operator {
+            ($name:ident, $operator:expr) => {
+                #[derive(Debug, Clone, Copy, QueryId, ValidGrouping)]
+                pub struct $name<L, R> {
+                    pub(crate) left: L,
+                    pub(crate) right: R,
+                }
+
+                impl<L, R> $name<L, R> {
+                    pub fn new(left: L, right: R) -> Self {
+                        $name { left, right }
+                    }
+                }
+
+                impl<L: Expression, R: Expression> Expression for $name<L, R> {
+                    type SqlType = Nullable<Ltree>;
+                }
+
+                impl<L, R> QueryFragment<Pg> for $name<L, R>
+                where
+                    L: QueryFragment<Pg>,
+                    R: QueryFragment<Pg>,
+                {
+                    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+                        self.left.walk_ast(out.reborrow())?;
+                        out.push_sql($operator);
+                        self.right.walk_ast(out.reborrow())?;
+                        Ok(())
+                    }
+                }
+
+                impl<L, R, QS> AppearsOnTable<QS> for $name<L, R>
+                where
+                    L: AppearsOnTable<QS>,
+                    R: AppearsOnTable<QS>,
+                    Self: Expression,
+                {
+                }
+
+                impl<L, R, QS> SelectableExpression<QS> for $name<L, R>
+                where
+                    L: SelectableExpression<QS>,
+                    R: SelectableExpression<QS>,
+                    Self: AppearsOnTable<QS>,
+                {
+                }
+            };
+        }
+
+        first_operator!(FirstContains, " ?@> ");
+        first_operator!(FirstContainedBy, " ?<@ ");
+        first_operator!(FirstMatches, " ?~ ");
+        first_operator!(FirstTMatches, " ?@ ");
     }
 
     use self::predicates::*;
 
+    /// Implemented by Diesel's nullability markers, giving each of the ltree
+    /// SQL types the same nullability as the expression they are used with.
+    pub trait LtreeNullability:
+        MaybeNullableType<Ltree>
+        + MaybeNullableType<Array<Ltree>>
+        + MaybeNullableType<Lquery>
+        + MaybeNullableType<Array<Lquery>>
+        + MaybeNullableType<Ltxtquery>
+        + MaybeNullableType<Array<Ltxtquery>>
+        + MaybeNullableType<Text>
+    {
+    }
+
+    impl<N> LtreeNullability for N where
+        N: MaybeNullableType<Ltree>
+            + MaybeNullableType<Array<Ltree>>
+            + MaybeNullableType<Lquery>
+            + MaybeNullableType<Array<Lquery>>
+            + MaybeNullableType<Ltxtquery>
+            + MaybeNullableType<Array<Ltxtquery>>
+            + MaybeNullableType<Text>
+    {
+    }
+
+    /// Implemented for the ltree extension's SQL types, `Text` and their
+    /// `Nullable` forms. `Nullability` is the same as `SqlType::IsNull`.
+    pub trait LtreeSqlType: SqlType {
+        type Nullability: LtreeNullability;
+    }
+
+    impl LtreeSqlType for Ltree {
+        type Nullability = NotNull;
+    }
+    impl LtreeSqlType for Nullable<Ltree> {
+        type Nullability = IsNullable;
+    }
+    impl LtreeSqlType for Array<Ltree> {
+        type Nullability = NotNull;
+    }
+    impl LtreeSqlType for Nullable<Array<Ltree>> {
+        type Nullability = IsNullable;
+    }
+    impl LtreeSqlType for Lquery {
+        type Nullability = NotNull;
+    }
+    impl LtreeSqlType for Nullable<Lquery> {
+        type Nullability = IsNullable;
+    }
+    impl LtreeSqlType for Array<Lquery> {
+        type Nullability = NotNull;
+    }
+    impl LtreeSqlType for Nullable<Array<Lquery>> {
+        type Nullability = IsNullable;
+    }
+    impl LtreeSqlType for Ltxtquery {
+        type Nullability = NotNull;
+    }
+    impl LtreeSqlType for Nullable<Ltxtquery> {
+        type Nullability = IsNullable;
+    }
+    impl LtreeSqlType for Array<Ltxtquery> {
+        type Nullability = NotNull;
+    }
+    impl LtreeSqlType for Nullable<Array<Ltxtquery>> {
+        type Nullability = IsNullable;
+    }
+    impl LtreeSqlType for Text {
+        type Nullability = NotNull;
+    }
+    impl LtreeSqlType for Nullable<Text> {
+        type Nullability = IsNullable;
+    }
+
+    /// `ST`, wrapped in `Nullable` if the SQL type of `E` is nullable.
+    pub type SameNullability<E, ST> =
+        MaybeNullable<<<E as Expression>::SqlType as LtreeSqlType>::Nullability, ST>;
+
+    /// Marker trait for `Ltree` and `Nullable<Ltree>`.
+    pub trait LtreeOrNullableLtree: LtreeSqlType {}
+
+    impl LtreeOrNullableLtree for Ltree {}
+    impl LtreeOrNullableLtree for Nullable<Ltree> {}
+
+    /// Marker trait for `Array<Ltree>` and `Nullable<Array<Ltree>>`.
+    pub trait LtreeArrayOrNullableLtreeArray: LtreeSqlType {}
+
+    impl LtreeArrayOrNullableLtreeArray for Array<Ltree> {}
+    impl LtreeArrayOrNullableLtreeArray for Nullable<Array<Ltree>> {}
+
+    /// Marker trait for `Lquery` and `Nullable<Lquery>`.
+    pub trait LqueryOrNullableLquery: LtreeSqlType {}
+
+    impl LqueryOrNullableLquery for Lquery {}
+    impl LqueryOrNullableLquery for Nullable<Lquery> {}
+
+    /// Marker trait for `Array<Lquery>` and `Nullable<Array<Lquery>>`.
+    pub trait LqueryArrayOrNullableLqueryArray: LtreeSqlType {}
+
+    impl LqueryArrayOrNullableLqueryArray for Array<Lquery> {}
+    impl LqueryArrayOrNullableLqueryArray for Nullable<Array<Lquery>> {}
+
+    /// Marker trait for `Ltxtquery` and `Nullable<Ltxtquery>`.
+    pub trait LtxtqueryOrNullableLtxtquery: LtreeSqlType {}
+
+    impl LtxtqueryOrNullableLtxtquery for Ltxtquery {}
+    impl LtxtqueryOrNullableLtxtquery for Nullable<Ltxtquery> {}
+
+    /// Marker trait for `Array<Ltxtquery>` and `Nullable<Array<Ltxtquery>>`.
+    pub trait LtxtqueryArrayOrNullableLtxtqueryArray: LtreeSqlType {}
+
+    impl LtxtqueryArrayOrNullableLtxtqueryArray for Array<Ltxtquery> {}
+    impl LtxtqueryArrayOrNullableLtxtqueryArray for Nullable<Array<Ltxtquery>> {}
+
+    /// Marker trait for `Text` and `Nullable<Text>`.
+    pub trait TextOrNullableText: LtreeSqlType {}
+
+    impl TextOrNullableText for Text {}
+    impl TextOrNullableText for Nullable<Text> {}
+
     /// Adds Ltree-specific extensions to queries.
-    pub trait LtreeExtensions: Expression<SqlType = Ltree> + Sized {
+    ///
+    /// `<`, `<=`, `>` and `>=` come from Diesel's `ExpressionMethods`, and
+    /// compare ltrees (and arrays of them) label by label like Postgres does.
+    pub trait LtreeExtensions: Expression + Sized
+    where
+        Self::SqlType: LtreeOrNullableLtree,
+    {
         /// Checks if the current expression contains another Ltree expression.
-        fn contains<T: AsExpression<Ltree>>(self, other: T) -> Contains<Self, T::Expression> {
+        fn contains<T: AsExpression<SameNullability<Self, Ltree>>>(
+            self,
+            other: T,
+        ) -> Contains<Self, T::Expression> {
             Contains::new(self, other.as_expression())
         }
 
         /// Checks if the current expression contains any Ltree expression in the given array.
-        fn contains_any<T: AsExpression<Array<Ltree>>>(
+        fn contains_any<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
             self,
             other: T,
         ) -> Contains<Self, T::Expression> {
@@ -151,7 +964,7 @@ pub mod dsl {
         }
 
         /// Checks if the current expression is contained by another Ltree expression.
-        fn contained_by<T: AsExpression<Ltree>>(
+        fn contained_by<T: AsExpression<SameNullability<Self, Ltree>>>(
             self,
             other: T,
         ) -> ContainedBy<Self, T::Expression> {
@@ -159,7 +972,7 @@ pub mod dsl {
         }
 
         /// Checks if the current expression is contained by any Ltree expression in the given array.
-        fn contained_by_any<T: AsExpression<Array<Ltree>>>(
+        fn contained_by_any<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
             self,
             other: T,
         ) -> ContainedBy<Self, T::Expression> {
@@ -167,12 +980,15 @@ pub mod dsl {
         }
 
         /// Checks if the current expression matches another Lquery expression.
-        fn matches<T: AsExpression<Lquery>>(self, other: T) -> Matches<Self, T::Expression> {
+        fn matches<T: AsExpression<SameNullability<Self, Lquery>>>(
+            self,
+            other: T,
+        ) -> Matches<Self, T::Expression> {
             Matches::new(self, other.as_expression())
         }
 
         /// Checks if the current expression matches any Lquery expression in the given array.
-        fn matches_any<T: AsExpression<Array<Lquery>>>(
+        fn matches_any<T: AsExpression<SameNullability<Self, Array<Lquery>>>>(
             self,
             other: T,
         ) -> MatchesAny<Self, T::Expression> {
@@ -180,25 +996,71 @@ pub mod dsl {
         }
 
         /// Checks if the current expression matches another Ltxtquery expression.-
-        fn tmatches<T: AsExpression<Ltxtquery>>(self, other: T) -> TMatches<Self, T::Expression> {
+        fn tmatches<T: AsExpression<SameNullability<Self, Ltxtquery>>>(
+            self,
+            other: T,
+        ) -> TMatches<Self, T::Expression> {
             TMatches::new(self, other.as_expression())
         }
 
+        /// Checks if the current expression matches any Ltxtquery expression in the given array.
+        fn tmatches_any<T: AsExpression<SameNullability<Self, Array<Ltxtquery>>>>(
+            self,
+            other: T,
+        ) -> TMatches<Self, AnyLtxtquery<T::Expression>> {
+            TMatches::new(self, AnyLtxtquery::new(other.as_expression()))
+        }
+
         /// Concatenates the current expression with another Ltree expression.
-        fn concat<T: AsExpression<Ltree>>(self, other: T) -> Concat<Self, T::Expression> {
+        fn concat<T: AsExpression<SameNullability<Self, Ltree>>>(
+            self,
+            other: T,
+        ) -> Concat<Self, T::Expression> {
+            Concat::new(self, other.as_expression())
+        }
+
+        /// Selects the first Ltree expression in the given array that is an ancestor of,
+        /// or equal to, the current expression, or `NULL` if there is none.
+        fn first_ancestor_in<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
+            self,
+            others: T,
+        ) -> FirstContains<T::Expression, Self> {
+            FirstContains::new(others.as_expression(), self)
+        }
+
+        /// Selects the first Ltree expression in the given array that is a descendant of,
+        /// or equal to, the current expression, or `NULL` if there is none.
+        fn first_descendant_in<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
+            self,
+            others: T,
+        ) -> FirstContainedBy<T::Expression, Self> {
+            FirstContainedBy::new(others.as_expression(), self)
+        }
+
+        /// Appends a text expression to the current expression, as `ltree || text`.
+        fn concat_text<T: AsExpression<SameNullability<Self, Text>>>(
+            self,
+            other: T,
+        ) -> Concat<Self, T::Expression> {
             Concat::new(self, other.as_expression())
         }
     }
 
     /// Adds Ltree-specific extensions to arrays of Ltree expressions.
-    pub trait LtreeArrayExtensions: Expression<SqlType = Array<Ltree>> + Sized {
+    pub trait LtreeArrayExtensions: Expression + Sized
+    where
+        Self::SqlType: LtreeArrayOrNullableLtreeArray,
+    {
         /// Checks if any Ltree expression in the array contains the specified Ltree expression.
-        fn any_contains<T: AsExpression<Ltree>>(self, other: T) -> Contains<Self, T::Expression> {
+        fn any_contains<T: AsExpression<SameNullability<Self, Ltree>>>(
+            self,
+            other: T,
+        ) -> Contains<Self, T::Expression> {
             Contains::new(self, other.as_expression())
         }
         
         /// Checks if any Ltree expression in the array is contained by the specified Ltree expression.
-        fn any_contained_by<T: AsExpression<Ltree>>(
+        fn any_contained_by<T: AsExpression<SameNullability<Self, Ltree>>>(
             self,
             other: T,
         ) -> ContainedBy<Self, T::Expression> {
@@ -206,12 +1068,15 @@ pub mod dsl {
         }
 
         /// Checks if any Ltree expression in the array matches the specified Lquery expression.
-        fn any_matches<T: AsExpression<Lquery>>(self, other: T) -> Matches<Self, T::Expression> {
+        fn any_matches<T: AsExpression<SameNullability<Self, Lquery>>>(
+            self,
+            other: T,
+        ) -> Matches<Self, T::Expression> {
             Matches::new(self, other.as_expression())
         }
 
         /// Checks if any Ltree expression in the array matches any Lquery expression in the given array.
-        fn any_matches_any<T: AsExpression<Array<Lquery>>>(
+        fn any_matches_any<T: AsExpression<SameNullability<Self, Array<Lquery>>>>(
             self,
             other: T,
         ) -> MatchesAny<Self, T::Expression> {
@@ -219,39 +1084,47 @@ pub mod dsl {
         }
         
         /// Checks if any Ltree expression in the array matches the specified Ltxtquery expression.
-        fn any_tmatches<T: AsExpression<Ltxtquery>>(
+        fn any_tmatches<T: AsExpression<SameNullability<Self, Ltxtquery>>>(
             self,
             other: T,
         ) -> TMatches<Self, T::Expression> {
             TMatches::new(self, other.as_expression())
         }
 
-        /// Checks if the first Ltree expression in the array contains the specified Ltree expression.
-        fn first_contains<T: AsExpression<Ltree>>(
+        /// Checks if any Ltree expression in the array matches any Ltxtquery expression in the given array.
+        fn any_tmatches_any<T: AsExpression<SameNullability<Self, Array<Ltxtquery>>>>(
+            self,
+            other: T,
+        ) -> TMatches<Self, AnyLtxtquery<T::Expression>> {
+            TMatches::new(self, AnyLtxtquery::new(other.as_expression()))
+        }
+
+        /// Returns the first Ltree expression in the array that contains the specified Ltree expression, or `NULL`.
+        fn first_contains<T: AsExpression<SameNullability<Self, Ltree>>>(
             self,
             other: T,
         ) -> FirstContains<Self, T::Expression> {
             FirstContains::new(self, other.as_expression())
         }
         
-        /// Checks if the first Ltree expression in the array is contained by the specified Ltree expression.
-        fn first_contained_by<T: AsExpression<Ltree>>(
+        /// Returns the first Ltree expression in the array that is contained by the specified Ltree expression, or `NULL`.
+        fn first_contained_by<T: AsExpression<SameNullability<Self, Ltree>>>(
             self,
             other: T,
         ) -> FirstContainedBy<Self, T::Expression> {
             FirstContainedBy::new(self, other.as_expression())
         }
 
-        /// Checks if the first Ltree expression in the array matches the specified Lquery expression.
-        fn first_matches<T: AsExpression<Lquery>>(
+        /// Returns the first Ltree expression in the array that matches the specified Lquery expression, or `NULL`.
+        fn first_matches<T: AsExpression<SameNullability<Self, Lquery>>>(
             self,
             other: T,
         ) -> FirstMatches<Self, T::Expression> {
             FirstMatches::new(self, other.as_expression())
         }
 
-        /// Checks if the first Ltree expression in the array matches the specified Ltxtquery expression.
-        fn first_tmatches<T: AsExpression<Ltxtquery>>(
+        /// Returns the first Ltree expression in the array that matches the specified Ltxtquery expression, or `NULL`.
+        fn first_tmatches<T: AsExpression<SameNullability<Self, Ltxtquery>>>(
             self,
             other: T,
         ) -> FirstTMatches<Self, T::Expression> {
@@ -260,30 +1133,51 @@ pub mod dsl {
     }
 
     /// Implements lquery extensions for diesel queries
-    pub trait LqueryExtensions: Expression<SqlType = Lquery> + Sized {
+    pub trait LqueryExtensions: Expression + Sized
+    where
+        Self::SqlType: LqueryOrNullableLquery,
+    {
         /// Checks if the current Lquery expression matches the specified Ltree expression.
-        fn matches<T: AsExpression<Ltree>>(self, other: T) -> Matches<Self, T::Expression> {
+        fn matches<T: AsExpression<SameNullability<Self, Ltree>>>(
+            self,
+            other: T,
+        ) -> Matches<Self, T::Expression> {
             Matches::new(self, other.as_expression())
         }
         
         /// Checks if the current Lquery expression matches any Ltree expression in the given array.
-        fn matches_any<T: AsExpression<Array<Ltree>>>(
+        fn matches_any<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
             self,
             other: T,
         ) -> Matches<Self, T::Expression> {
             Matches::new(self, other.as_expression())
         }
+
+        /// Selects the first Ltree expression in the given array matched by the current
+        /// Lquery expression, or `NULL` if there is none.
+        fn first_match_in<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
+            self,
+            others: T,
+        ) -> FirstMatches<T::Expression, Self> {
+            FirstMatches::new(others.as_expression(), self)
+        }
     }
     
     /// Adds Lquery-specific extensions to arrays of Lquery expressions.
-    pub trait LqueryArrayExtensions: Expression<SqlType = Array<Lquery>> + Sized {
+    pub trait LqueryArrayExtensions: Expression + Sized
+    where
+        Self::SqlType: LqueryArrayOrNullableLqueryArray,
+    {
         /// Checks if any Lquery expression in the array matches the specified Ltree expression.
-        fn any_matches<T: AsExpression<Ltree>>(self, other: T) -> MatchesAny<Self, T::Expression> {
+        fn any_matches<T: AsExpression<SameNullability<Self, Ltree>>>(
+            self,
+            other: T,
+        ) -> MatchesAny<Self, T::Expression> {
             MatchesAny::new(self, other.as_expression())
         }
 
         /// Checks if any Lquery expression in the array matches any Ltree expression in the given array.
-        fn any_matches_any<T: AsExpression<Array<Ltree>>>(
+        fn any_matches_any<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
             self,
             other: T,
         ) -> MatchesAny<Self, T::Expression> {
@@ -292,26 +1186,114 @@ pub mod dsl {
     }
 
     /// A trait for adding Ltxtquery-specific extensions to queries.
-    pub trait LtxtqueryExtensions: Expression<SqlType = Ltxtquery> + Sized {
+    pub trait LtxtqueryExtensions: Expression + Sized
+    where
+        Self::SqlType: LtxtqueryOrNullableLtxtquery,
+    {
         /// Checks if the current Ltxtquery expression matches the specified Ltree expression.
-        fn tmatches<T: AsExpression<Ltree>>(self, other: T) -> TMatches<Self, T::Expression> {
+        fn tmatches<T: AsExpression<SameNullability<Self, Ltree>>>(
+            self,
+            other: T,
+        ) -> TMatches<Self, T::Expression> {
             TMatches::new(self, other.as_expression())
         }
         
         /// Checks if the current Ltxtquery expression matches any Ltree expression in the given array.
-        fn tmatches_any<T: AsExpression<Array<Ltree>>>(
+        fn tmatches_any<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
             self,
             other: T,
         ) -> TMatches<Self, T::Expression> {
             TMatches::new(self, other.as_expression())
         }
+
+        /// Selects the first Ltree expression in the given array matched by the current
+        /// Ltxtquery expression, or `NULL` if there is none.
+        fn first_tmatch_in<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
+            self,
+            others: T,
+        ) -> FirstTMatches<T::Expression, Self> {
+            FirstTMatches::new(others.as_expression(), self)
+        }
+    }
+
+    /// Adds Ltxtquery-specific extensions to arrays of Ltxtquery expressions.
+    pub trait LtxtqueryArrayExtensions: Expression + Sized
+    where
+        Self::SqlType: LtxtqueryArrayOrNullableLtxtqueryArray,
+    {
+        /// Checks if any Ltxtquery expression in the array matches the specified Ltree expression.
+        fn any_tmatches<T: AsExpression<SameNullability<Self, Ltree>>>(
+            self,
+            other: T,
+        ) -> TMatches<T::Expression, AnyLtxtquery<Self>> {
+            TMatches::new(other.as_expression(), AnyLtxtquery::new(self))
+        }
+
+        /// Checks if any Ltxtquery expression in the array matches any Ltree expression in the given array.
+        fn any_tmatches_any<T: AsExpression<SameNullability<Self, Array<Ltree>>>>(
+            self,
+            other: T,
+        ) -> TMatches<T::Expression, AnyLtxtquery<Self>> {
+            TMatches::new(other.as_expression(), AnyLtxtquery::new(self))
+        }
+    }
+
+    /// Adds Ltree-specific extensions to text expressions.
+    pub trait TextLtreeExtensions: Expression + Sized
+    where
+        Self::SqlType: TextOrNullableText,
+    {
+        /// Prepends the current text expression to an Ltree expression, as `text || ltree`.
+        fn concat_ltree<T: AsExpression<SameNullability<Self, Ltree>>>(
+            self,
+            other: T,
+        ) -> Concat<Self, T::Expression> {
+            Concat::new(self, other.as_expression())
+        }
     }
 
-    impl<T: Expression<SqlType = Ltree>> LtreeExtensions for T {}
-    impl<T: Expression<SqlType = Array<Ltree>>> LtreeArrayExtensions for T {}
-    impl<T: Expression<SqlType = Lquery>> LqueryExtensions for T {}
-    impl<T: Expression<SqlType = Array<Lquery>>> LqueryArrayExtensions for T {}
-    impl<T: Expression<SqlType = Ltxtquery>> LtxtqueryExtensions for T {}
+    impl<T> LtreeExtensions for T
+    where
+        T: Expression,
+        T::SqlType: LtreeOrNullableLtree,
+    {
+    }
+    impl<T> LtreeArrayExtensions for T
+    where
+        T: Expression,
+        T::SqlType: LtreeArrayOrNullableLtreeArray,
+    {
+    }
+    impl<T> LqueryExtensions for T
+    where
+        T: Expression,
+        T::SqlType: LqueryOrNullableLquery,
+    {
+    }
+    impl<T> LqueryArrayExtensions for T
+    where
+        T: Expression,
+        T::SqlType: LqueryArrayOrNullableLqueryArray,
+    {
+    }
+    impl<T> LtxtqueryExtensions for T
+    where
+        T: Expression,
+        T::SqlType: LtxtqueryOrNullableLtxtquery,
+    {
+    }
+    impl<T> LtxtqueryArrayExtensions for T
+    where
+        T: Expression,
+        T::SqlType: LtxtqueryArrayOrNullableLtxtqueryArray,
+    {
+    }
+    impl<T> TextLtreeExtensions for T
+    where
+        T: Expression,
+        T::SqlType: TextOrNullableText,
+    {
+    }
 }
 
 pub use crate::dsl::*;
@@ -0,0 +1,759 @@
+//! A typed representation of [lquery](https://www.postgresql.org/docs/current/ltree.html#LTREE-DEFINITIONS)
+//! patterns.
+//!
+//! [`Query`] can be parsed from text, built up in Rust, and printed back to the
+//! same canonical text Postgres produces. It converts into
+//! [`values::Lquery`] for use with
+//! [`LtreeExtensions::matches`](crate::dsl::LtreeExtensions::matches).
+use std::fmt;
+use std::str::FromStr;
+
+use crate::values::{self, is_label_char, Ltree, MAX_LABEL_LENGTH, MAX_LEVELS};
+
+/// A parsed lquery: a sequence of levels separated by `.`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Query {
+    pub levels: Vec<Level>,
+}
+
+/// A single level of an lquery.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Level {
+    /// `*`, matching any number of labels within the quantifier's bounds.
+    Any(Quantifier),
+    /// One or more `|`-separated alternatives, each matching a single label.
+    Match {
+        /// Whether the level is prefixed with `!`, matching any label that
+        /// matches none of the alternatives.
+        negated: bool,
+        variants: Vec<Variant>,
+        /// How many times the level may repeat, if given as `{n,m}`.
+        quantifier: Option<Quantifier>,
+    },
+}
+
+/// One alternative of a [`Level::Match`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Variant {
+    pub label: String,
+    /// `*`: match labels starting with `label`.
+    pub prefix: bool,
+    /// `@`: match case-insensitively.
+    pub case_insensitive: bool,
+    /// `%`: match underscore-separated words rather than the whole label.
+    pub word: bool,
+}
+
+/// The `{n,m}` bounds on how many labels a level matches.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Quantifier {
+    pub low: u16,
+    /// The upper bound, or `None` if unbounded.
+    pub high: Option<u16>,
+}
+
+impl Quantifier {
+    /// Matches any number of labels, `{,}`.
+    pub const ANY: Quantifier = Quantifier { low: 0, high: None };
+
+    /// Matches exactly `n` labels, `{n}`.
+    pub fn exactly(n: u16) -> Quantifier {
+        Quantifier {
+            low: n,
+            high: Some(n),
+        }
+    }
+
+    /// Matches between `low` and `high` labels inclusive, `{low,high}`.
+    pub fn between(low: u16, high: u16) -> Quantifier {
+        Quantifier {
+            low,
+            high: Some(high),
+        }
+    }
+
+    /// Matches `low` or more labels, `{low,}`.
+    pub fn at_least(low: u16) -> Quantifier {
+        Quantifier { low, high: None }
+    }
+
+    /// Treats an upper bound of [`MAX_LEVELS`] as unbounded, as Postgres
+    /// does, unless the bounds are equal.
+    fn normalized(self) -> Quantifier {
+        match self.high {
+            Some(high) if high as usize == MAX_LEVELS && high != self.low => Quantifier {
+                low: self.low,
+                high: None,
+            },
+            _ => self,
+        }
+    }
+}
+
+impl Variant {
+    /// Matches `label` exactly.
+    pub fn new<S: Into<String>>(label: S) -> Variant {
+        Variant {
+            label: label.into(),
+            prefix: false,
+            case_insensitive: false,
+            word: false,
+        }
+    }
+
+    /// Sets the `*` flag.
+    pub fn prefix(mut self) -> Variant {
+        self.prefix = true;
+        self
+    }
+
+    /// Sets the `@` flag.
+    pub fn ci(mut self) -> Variant {
+        self.case_insensitive = true;
+        self
+    }
+
+    /// Sets the `%` flag.
+    pub fn word(mut self) -> Variant {
+        self.word = true;
+        self
+    }
+}
+
+impl Level {
+    /// `*`
+    pub fn any() -> Level {
+        Level::Any(Quantifier::ANY)
+    }
+
+    /// A level matching a single label exactly.
+    pub fn label<S: Into<String>>(label: S) -> Level {
+        Level::one_of(vec![Variant::new(label)])
+    }
+
+    /// A level matching any of `variants`.
+    pub fn one_of(variants: Vec<Variant>) -> Level {
+        Level::Match {
+            negated: false,
+            variants,
+            quantifier: None,
+        }
+    }
+
+    /// Negates a [`Level::Match`]. Has no effect on [`Level::Any`].
+    pub fn negate(self) -> Level {
+        match self {
+            Level::Match {
+                negated,
+                variants,
+                quantifier,
+            } => Level::Match {
+                negated: !negated,
+                variants,
+                quantifier,
+            },
+            any => any,
+        }
+    }
+
+    /// Sets the level's quantifier.
+    pub fn repeat(self, q: Quantifier) -> Level {
+        match self {
+            Level::Any(_) => Level::Any(q),
+            Level::Match {
+                negated, variants, ..
+            } => Level::Match {
+                negated,
+                variants,
+                quantifier: Some(q),
+            },
+        }
+    }
+}
+
+impl Query {
+    pub fn new(levels: Vec<Level>) -> Query {
+        Query { levels }
+    }
+
+    /// Parses an lquery, applying the same rules as the ltree extension's
+    /// input function.
+    pub fn parse(s: &str) -> Result<Query, ParseError> {
+        Parser::new(s).parse()
+    }
+
+    /// Checks whether `path` matches the query, like the `~` operator.
+    pub fn is_match(&self, path: &Ltree) -> bool {
+        let labels = path.labels().map(|l| l.as_str()).collect::<Vec<_>>();
+        check_levels(&self.levels, &labels)
+    }
+
+    /// Checks whether `path` matches any of `queries`, like the `?` operator.
+    pub fn is_match_any(queries: &[Query], path: &Ltree) -> bool {
+        queries.iter().any(|query| query.is_match(path))
+    }
+}
+
+impl values::Lquery {
+    /// Checks whether `path` matches the query, like the `~` operator.
+    ///
+    /// A query that does not [parse](Query::parse) matches nothing, where
+    /// Postgres would reject it instead.
+    pub fn is_match(&self, path: &Ltree) -> bool {
+        Query::parse(&self.0).is_ok_and(|query| query.is_match(path))
+    }
+
+    /// Checks whether `path` matches any of `queries`, like the `?` operator.
+    pub fn is_match_any(queries: &[values::Lquery], path: &Ltree) -> bool {
+        queries.iter().any(|query| query.is_match(path))
+    }
+}
+
+/// Matches `labels` against `levels`, backtracking over the possible repeat
+/// counts of each level in the same order as the ltree extension.
+fn check_levels(mut levels: &[Level], mut labels: &[&str]) -> bool {
+    while let Some((level, rest)) = levels.split_first() {
+        let (low, high) = match level {
+            Level::Any(q)
+            | Level::Match {
+                quantifier: Some(q),
+                ..
+            } => (q.low as usize, q.high.map_or(MAX_LEVELS, usize::from)),
+            Level::Match { .. } => (1, 1),
+        };
+        let high = high.min(labels.len());
+        if high < low {
+            return false;
+        }
+
+        for count in 0..high {
+            if count >= low && check_levels(rest, labels) {
+                return true;
+            }
+            if !level.matches_label(labels[0]) {
+                return false;
+            }
+            labels = &labels[1..];
+        }
+        levels = rest;
+    }
+    labels.is_empty()
+}
+
+impl Level {
+    fn matches_label(&self, label: &str) -> bool {
+        match self {
+            Level::Any(_) => true,
+            Level::Match {
+                negated, variants, ..
+            } => {
+                variants
+                    .iter()
+                    .any(|v| label_matches(&v.label, label, v.prefix, v.case_insensitive, v.word))
+                    != *negated
+            }
+        }
+    }
+}
+
+/// Compares a single query word against a label, honouring the `*`, `@` and
+/// `%` flags.
+pub(crate) fn label_matches(
+    pattern: &str,
+    label: &str,
+    prefix: bool,
+    case_insensitive: bool,
+    word: bool,
+) -> bool {
+    let eq = |a: &[u8], b: &[u8]| match case_insensitive {
+        true => a.eq_ignore_ascii_case(b),
+        false => a == b,
+    };
+    let matches = |pattern: &[u8], label: &[u8]| {
+        (label.len() == pattern.len() || (label.len() > pattern.len() && prefix))
+            && eq(pattern, &label[..pattern.len()])
+    };
+
+    if word {
+        // Every `_`-separated word of the pattern has to match some word of
+        // the label.
+        fn words(s: &str) -> impl Iterator<Item = &str> {
+            s.split('_').filter(|w| !w.is_empty())
+        }
+        words(pattern).all(|p| words(label).any(|l| matches(p.as_bytes(), l.as_bytes())))
+    } else {
+        matches(pattern.as_bytes(), label.as_bytes())
+    }
+}
+
+impl FromStr for Query {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+impl TryFrom<&values::Lquery> for Query {
+    type Error = ParseError;
+
+    fn try_from(lquery: &values::Lquery) -> Result<Self, Self::Error> {
+        Query::parse(&lquery.0)
+    }
+}
+
+impl From<&Query> for values::Lquery {
+    fn from(query: &Query) -> Self {
+        values::Lquery(query.to_string())
+    }
+}
+
+impl From<Query> for values::Lquery {
+    fn from(query: Query) -> Self {
+        values::Lquery::from(&query)
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, level) in self.levels.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", level)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Level::Any(q) => {
+                f.write_str("*")?;
+                // A bare `*` already means `{,}`.
+                if q.normalized() != Quantifier::ANY {
+                    write!(f, "{}", q)?;
+                }
+            }
+            Level::Match {
+                negated,
+                variants,
+                quantifier,
+            } => {
+                if *negated {
+                    f.write_str("!")?;
+                }
+                for (i, variant) in variants.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str("|")?;
+                    }
+                    write!(f, "{}", variant)?;
+                }
+                if let Some(q) = quantifier {
+                    write!(f, "{}", q)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.label)?;
+        if self.word {
+            f.write_str("%")?;
+        }
+        if self.case_insensitive {
+            f.write_str("@")?;
+        }
+        if self.prefix {
+            f.write_str("*")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Quantifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let q = self.normalized();
+        match (q.low, q.high) {
+            (low, Some(high)) if low == high => write!(f, "{{{}}}", low),
+            (0, None) => f.write_str("{,}"),
+            (0, Some(high)) => write!(f, "{{,{}}}", high),
+            (low, None) => write!(f, "{{{},}}", low),
+            (low, Some(high)) => write!(f, "{{{},{}}}", low, high),
+        }
+    }
+}
+
+/// What the parser expected to find where it failed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Expected {
+    /// A label, `!` or `*` at the start of a level.
+    Level,
+    /// A label after `!` or `|`.
+    Label,
+    /// A flag, `|`, `{` or `.` after a label's flags.
+    LabelDelimiter,
+    /// `{` or `.` after `*`.
+    StarDelimiter,
+    /// A number or `,` after `{`.
+    LowerBound,
+    /// A digit, `,` or `}` within the lower bound.
+    BoundSeparator,
+    /// A number or `}` after `,`.
+    UpperBound,
+    /// A digit or `}` within the upper bound.
+    CloseBrace,
+    /// `.` after a quantifier.
+    Dot,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Expected::Level => "a label, `!` or `*`",
+            Expected::Label => "a label",
+            Expected::LabelDelimiter => "a flag, `|`, `{` or `.`",
+            Expected::StarDelimiter => "`{` or `.`",
+            Expected::LowerBound => "a number or `,`",
+            Expected::BoundSeparator => "a digit, `,` or `}`",
+            Expected::UpperBound => "a number or `}`",
+            Expected::CloseBrace => "a digit or `}`",
+            Expected::Dot => "`.`",
+        })
+    }
+}
+
+/// Why a string could not be parsed as a [`Query`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParseError {
+    /// An unexpected character, or the end of input if `found` is `None`.
+    Unexpected {
+        offset: usize,
+        found: Option<char>,
+        expected: Expected,
+    },
+    /// A label longer than [`MAX_LABEL_LENGTH`] characters.
+    LabelTooLong { offset: usize, length: usize },
+    /// More than [`MAX_LEVELS`] levels.
+    TooManyLevels { offset: usize },
+    /// A quantifier bound greater than [`MAX_LEVELS`].
+    BoundTooLarge { offset: usize },
+    /// A quantifier whose lower bound is greater than its upper bound.
+    BoundsOutOfOrder { offset: usize, low: u16, high: u16 },
+}
+
+impl ParseError {
+    /// The byte offset into the input at which the error was found.
+    pub fn offset(&self) -> usize {
+        match *self {
+            ParseError::Unexpected { offset, .. }
+            | ParseError::LabelTooLong { offset, .. }
+            | ParseError::TooManyLevels { offset }
+            | ParseError::BoundTooLarge { offset }
+            | ParseError::BoundsOutOfOrder { offset, .. } => offset,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ParseError::Unexpected {
+                offset,
+                found: Some(c),
+                expected,
+            } => write!(
+                f,
+                "unexpected {:?} in lquery at byte {}, expected {}",
+                c, offset, expected
+            ),
+            ParseError::Unexpected {
+                offset,
+                found: None,
+                expected,
+            } => write!(
+                f,
+                "unexpected end of lquery at byte {}, expected {}",
+                offset, expected
+            ),
+            ParseError::LabelTooLong { offset, length } => write!(
+                f,
+                "lquery label at byte {} is {} characters long, the maximum is {}",
+                offset, length, MAX_LABEL_LENGTH
+            ),
+            ParseError::TooManyLevels { offset } => write!(
+                f,
+                "lquery has more than {} levels, starting at byte {}",
+                MAX_LEVELS, offset
+            ),
+            ParseError::BoundTooLarge { offset } => write!(
+                f,
+                "lquery quantifier bound at byte {} exceeds the maximum of {}",
+                offset, MAX_LEVELS
+            ),
+            ParseError::BoundsOutOfOrder { offset, low, high } => write!(
+                f,
+                "lquery quantifier at byte {} has lower bound {} greater than upper bound {}",
+                offset, low, high
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The states of the ltree extension's lquery parser, each named for what it
+/// waits for next.
+#[derive(Clone, Copy)]
+enum State {
+    Level,
+    Variant,
+    Delimiter,
+    Open,
+    Low,
+    LowEnd,
+    High,
+    HighEnd,
+    End,
+}
+
+impl State {
+    fn expected(self) -> Expected {
+        match self {
+            State::Level => Expected::Level,
+            State::Variant => Expected::Label,
+            State::Delimiter => Expected::LabelDelimiter,
+            State::Open => Expected::StarDelimiter,
+            State::Low => Expected::LowerBound,
+            State::LowEnd => Expected::BoundSeparator,
+            State::High => Expected::UpperBound,
+            State::HighEnd => Expected::CloseBrace,
+            State::End => Expected::Dot,
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    levels: Vec<Level>,
+    negated: bool,
+    variants: Vec<Variant>,
+    star: bool,
+    label_start: usize,
+    bound_start: usize,
+    low: u32,
+    high: Option<u32>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser {
+            input,
+            levels: Vec::new(),
+            negated: false,
+            variants: Vec::new(),
+            star: false,
+            label_start: 0,
+            bound_start: 0,
+            low: 0,
+            high: None,
+        }
+    }
+
+    fn parse(mut self) -> Result<Query, ParseError> {
+        let mut state = State::Level;
+
+        for (offset, c) in self.input.char_indices() {
+            let unexpected = |state: State| ParseError::Unexpected {
+                offset,
+                found: Some(c),
+                expected: state.expected(),
+            };
+            state = match state {
+                State::Level if is_label_char(c) => self.start_label(offset),
+                State::Level if c == '!' => {
+                    self.negated = true;
+                    State::Variant
+                }
+                State::Level if c == '*' => {
+                    self.star = true;
+                    State::Open
+                }
+                State::Variant if is_label_char(c) => self.start_label(offset),
+                State::Delimiter => match c {
+                    '@' | '*' | '%' => {
+                        self.finish_label(offset)?;
+                        let variant = self.variants.last_mut().unwrap();
+                        match c {
+                            '@' => variant.case_insensitive = true,
+                            '*' => variant.prefix = true,
+                            _ => variant.word = true,
+                        }
+                        State::Delimiter
+                    }
+                    '|' => {
+                        self.finish_label(offset)?;
+                        State::Variant
+                    }
+                    '{' => {
+                        self.finish_label(offset)?;
+                        self.start_bounds(offset)
+                    }
+                    '.' => {
+                        self.finish_label(offset)?;
+                        self.finish_level(offset, None)?
+                    }
+                    c if is_label_char(c) && !self.has_flags() => State::Delimiter,
+                    _ => return Err(unexpected(state)),
+                },
+                State::Open if c == '{' => self.start_bounds(offset),
+                State::Open if c == '.' => self.finish_level(offset, None)?,
+                State::Low if c.is_ascii_digit() => {
+                    self.low = self.digit(self.low, c)?;
+                    State::LowEnd
+                }
+                State::Low if c == ',' => State::High,
+                State::LowEnd if c.is_ascii_digit() => {
+                    self.low = self.digit(self.low, c)?;
+                    State::LowEnd
+                }
+                State::LowEnd if c == ',' => State::High,
+                State::LowEnd if c == '}' => {
+                    self.high = Some(self.low);
+                    State::End
+                }
+                State::High | State::HighEnd if c.is_ascii_digit() => {
+                    self.high = Some(self.digit(self.high.unwrap_or(0), c)?);
+                    State::HighEnd
+                }
+                State::High | State::HighEnd if c == '}' => State::End,
+                State::End if c == '.' => {
+                    let quantifier = self.quantifier()?;
+                    self.finish_level(offset, Some(quantifier))?
+                }
+                _ => return Err(unexpected(state)),
+            };
+        }
+
+        let end = self.input.len();
+        match state {
+            State::Delimiter => {
+                self.finish_label(end)?;
+                self.finish_level(end, None)?;
+            }
+            State::Open => {
+                self.finish_level(end, None)?;
+            }
+            State::End => {
+                let quantifier = self.quantifier()?;
+                self.finish_level(end, Some(quantifier))?;
+            }
+            state => {
+                return Err(ParseError::Unexpected {
+                    offset: end,
+                    found: None,
+                    expected: state.expected(),
+                })
+            }
+        }
+
+        Ok(Query {
+            levels: self.levels,
+        })
+    }
+
+    fn start_label(&mut self, offset: usize) -> State {
+        self.label_start = offset;
+        self.variants.push(Variant::new(String::new()));
+        State::Delimiter
+    }
+
+    /// Whether the current variant's label has been followed by any flags.
+    fn has_flags(&self) -> bool {
+        let variant = self.variants.last().unwrap();
+        variant.prefix || variant.case_insensitive || variant.word
+    }
+
+    /// Fills in the label of the current variant, if it is still empty.
+    fn finish_label(&mut self, end: usize) -> Result<(), ParseError> {
+        let variant = self.variants.last_mut().unwrap();
+        if variant.label.is_empty() {
+            let label = &self.input[self.label_start..end];
+            let length = label.chars().count();
+            if length > MAX_LABEL_LENGTH {
+                return Err(ParseError::LabelTooLong {
+                    offset: self.label_start,
+                    length,
+                });
+            }
+            variant.label = label.to_string();
+        }
+        Ok(())
+    }
+
+    fn start_bounds(&mut self, offset: usize) -> State {
+        self.bound_start = offset;
+        self.low = 0;
+        self.high = None;
+        State::Low
+    }
+
+    fn digit(&self, n: u32, c: char) -> Result<u32, ParseError> {
+        let n = n * 10 + c.to_digit(10).unwrap();
+        if n > MAX_LEVELS as u32 {
+            return Err(ParseError::BoundTooLarge {
+                offset: self.bound_start,
+            });
+        }
+        Ok(n)
+    }
+
+    fn quantifier(&self) -> Result<Quantifier, ParseError> {
+        // Both bounds are known to be at most MAX_LEVELS, so fit in a u16.
+        let quantifier = Quantifier {
+            low: self.low as u16,
+            high: self.high.map(|h| h as u16),
+        };
+        if let Quantifier {
+            low,
+            high: Some(high),
+        } = quantifier
+        {
+            if low > high {
+                return Err(ParseError::BoundsOutOfOrder {
+                    offset: self.bound_start,
+                    low,
+                    high,
+                });
+            }
+        }
+        Ok(quantifier.normalized())
+    }
+
+    fn finish_level(
+        &mut self,
+        offset: usize,
+        quantifier: Option<Quantifier>,
+    ) -> Result<State, ParseError> {
+        if self.levels.len() == MAX_LEVELS {
+            return Err(ParseError::TooManyLevels { offset });
+        }
+        let level = if self.star {
+            Level::Any(quantifier.unwrap_or(Quantifier::ANY))
+        } else {
+            Level::Match {
+                negated: self.negated,
+                variants: std::mem::take(&mut self.variants),
+                quantifier,
+            }
+        };
+        self.levels.push(level);
+        self.negated = false;
+        self.star = false;
+        Ok(State::Level)
+    }
+}
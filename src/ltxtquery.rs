@@ -0,0 +1,445 @@
+//! A typed representation of [ltxtquery](https://www.postgresql.org/docs/current/ltree.html#LTREE-DEFINITIONS)
+//! expressions.
+//!
+//! [`Txt`] can be parsed from text, or composed in Rust with the `&`, `|` and
+//! `!` operators:
+//!
+//! ```
+//! use diesel_ltree::ltxtquery::Txt;
+//!
+//! let query = Txt::word("Europe").ci() & !Txt::word("Russia");
+//! assert_eq!(query.to_string(), "Europe@ & !Russia");
+//! ```
+//!
+//! It prints back to the same canonical text Postgres produces, and converts
+//! into [`values::Ltxtquery`] for use with
+//! [`LtreeExtensions::tmatches`](crate::dsl::LtreeExtensions::tmatches).
+use std::fmt;
+use std::ops::{BitAnd, BitOr, Not};
+use std::str::FromStr;
+
+use crate::lquery::label_matches;
+use crate::values::{self, is_label_char, Ltree, MAX_LABEL_LENGTH};
+
+/// A parsed ltxtquery: a boolean expression over words.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Txt {
+    Word(Word),
+    Not(Box<Txt>),
+    And(Box<Txt>, Box<Txt>),
+    Or(Box<Txt>, Box<Txt>),
+}
+
+/// A word of an ltxtquery, matched against each label of a path.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Word {
+    pub label: String,
+    /// `*`: match labels starting with `label`.
+    pub prefix: bool,
+    /// `@`: match case-insensitively.
+    pub case_insensitive: bool,
+    /// `%`: match underscore-separated words rather than the whole label.
+    pub word: bool,
+}
+
+impl Word {
+    /// Matches `label` exactly.
+    pub fn new<S: Into<String>>(label: S) -> Word {
+        Word {
+            label: label.into(),
+            prefix: false,
+            case_insensitive: false,
+            word: false,
+        }
+    }
+}
+
+impl Txt {
+    /// A query matching paths with a label equal to `label`.
+    pub fn word<S: Into<String>>(label: S) -> Txt {
+        Txt::Word(Word::new(label))
+    }
+
+    /// Parses an ltxtquery, applying the same rules as the ltree extension's
+    /// input function.
+    pub fn parse(s: &str) -> Result<Txt, ParseError> {
+        let tokens = tokenize(s)?;
+        let mut tokens = tokens.into_iter().peekable();
+        Ok(parse_or(&mut tokens))
+    }
+
+    /// Checks whether `path` matches the query, like the `@` operator.
+    ///
+    /// A word is true if it matches any label of the path.
+    pub fn is_match(&self, path: &Ltree) -> bool {
+        match self {
+            Txt::Word(w) => path.labels().any(|label| {
+                label_matches(
+                    &w.label,
+                    label.as_str(),
+                    w.prefix,
+                    w.case_insensitive,
+                    w.word,
+                )
+            }),
+            Txt::Not(q) => !q.is_match(path),
+            Txt::And(l, r) => l.is_match(path) && r.is_match(path),
+            Txt::Or(l, r) => l.is_match(path) || r.is_match(path),
+        }
+    }
+
+    /// Sets the `*` flag on every word in the query.
+    pub fn prefix(self) -> Txt {
+        self.map_words(&|w| w.prefix = true)
+    }
+
+    /// Sets the `@` flag on every word in the query.
+    pub fn ci(self) -> Txt {
+        self.map_words(&|w| w.case_insensitive = true)
+    }
+
+    /// Sets the `%` flag on every word in the query.
+    pub fn word_match(self) -> Txt {
+        self.map_words(&|w| w.word = true)
+    }
+
+    fn map_words(self, f: &dyn Fn(&mut Word)) -> Txt {
+        match self {
+            Txt::Word(mut w) => {
+                f(&mut w);
+                Txt::Word(w)
+            }
+            Txt::Not(q) => Txt::Not(Box::new(q.map_words(f))),
+            Txt::And(l, r) => Txt::And(Box::new(l.map_words(f)), Box::new(r.map_words(f))),
+            Txt::Or(l, r) => Txt::Or(Box::new(l.map_words(f)), Box::new(r.map_words(f))),
+        }
+    }
+
+    /// Prints the query the way Postgres does: `|` is parenthesized unless it
+    /// is the outermost operator, as is any operator under `!`.
+    fn fmt_infix(&self, f: &mut fmt::Formatter<'_>, first: bool) -> fmt::Result {
+        match self {
+            Txt::Word(w) => write!(f, "{}", w),
+            Txt::Not(q) => match **q {
+                Txt::Word(_) => write!(f, "!{}", q),
+                _ => {
+                    f.write_str("!( ")?;
+                    q.fmt_infix(f, true)?;
+                    f.write_str(" )")
+                }
+            },
+            Txt::And(l, r) => {
+                l.fmt_infix(f, false)?;
+                f.write_str(" & ")?;
+                r.fmt_infix(f, false)
+            }
+            Txt::Or(l, r) => {
+                if !first {
+                    f.write_str("( ")?;
+                }
+                l.fmt_infix(f, false)?;
+                f.write_str(" | ")?;
+                r.fmt_infix(f, false)?;
+                if !first {
+                    f.write_str(" )")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl values::Ltxtquery {
+    /// Checks whether `path` matches the query, like the `@` operator.
+    ///
+    /// A query that does not [parse](Txt::parse) matches nothing, where
+    /// Postgres would reject it instead.
+    pub fn is_match(&self, path: &Ltree) -> bool {
+        Txt::parse(&self.0).is_ok_and(|txt| txt.is_match(path))
+    }
+}
+
+impl BitAnd for Txt {
+    type Output = Txt;
+
+    fn bitand(self, rhs: Txt) -> Txt {
+        Txt::And(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl BitOr for Txt {
+    type Output = Txt;
+
+    fn bitor(self, rhs: Txt) -> Txt {
+        Txt::Or(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Not for Txt {
+    type Output = Txt;
+
+    fn not(self) -> Txt {
+        Txt::Not(Box::new(self))
+    }
+}
+
+impl FromStr for Txt {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Txt::parse(s)
+    }
+}
+
+impl TryFrom<&values::Ltxtquery> for Txt {
+    type Error = ParseError;
+
+    fn try_from(ltxtquery: &values::Ltxtquery) -> Result<Self, Self::Error> {
+        Txt::parse(&ltxtquery.0)
+    }
+}
+
+impl From<&Txt> for values::Ltxtquery {
+    fn from(txt: &Txt) -> Self {
+        values::Ltxtquery(txt.to_string())
+    }
+}
+
+impl From<Txt> for values::Ltxtquery {
+    fn from(txt: Txt) -> Self {
+        values::Ltxtquery::from(&txt)
+    }
+}
+
+impl fmt::Display for Txt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_infix(f, true)
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.label)?;
+        if self.word {
+            f.write_str("%")?;
+        }
+        if self.case_insensitive {
+            f.write_str("@")?;
+        }
+        if self.prefix {
+            f.write_str("*")?;
+        }
+        Ok(())
+    }
+}
+
+/// What the parser expected to find where it failed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Expected {
+    /// A word, `!` or `(`.
+    Operand,
+    /// A flag or an operator after a word's flags.
+    Modifier,
+    /// `&`, `|`, `)` or the end of the query.
+    Operator,
+    /// `)` to close an open parenthesis.
+    CloseParen,
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Expected::Operand => "a word, `!` or `(`",
+            Expected::Modifier => "a flag or an operator",
+            Expected::Operator => "`&`, `|`, `)` or the end of the query",
+            Expected::CloseParen => "`)`",
+        })
+    }
+}
+
+/// Why a string could not be parsed as a [`Txt`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParseError {
+    /// An unexpected character, or the end of input if `found` is `None`.
+    Unexpected {
+        offset: usize,
+        found: Option<char>,
+        expected: Expected,
+    },
+    /// A word longer than [`MAX_LABEL_LENGTH`] characters.
+    WordTooLong { offset: usize, length: usize },
+}
+
+impl ParseError {
+    /// The byte offset into the input at which the error was found.
+    pub fn offset(&self) -> usize {
+        match *self {
+            ParseError::Unexpected { offset, .. } | ParseError::WordTooLong { offset, .. } => {
+                offset
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ParseError::Unexpected {
+                offset,
+                found: Some(c),
+                expected,
+            } => write!(
+                f,
+                "unexpected {:?} in ltxtquery at byte {}, expected {}",
+                c, offset, expected
+            ),
+            ParseError::Unexpected {
+                offset,
+                found: None,
+                expected,
+            } => write!(
+                f,
+                "unexpected end of ltxtquery at byte {}, expected {}",
+                offset, expected
+            ),
+            ParseError::WordTooLong { offset, length } => write!(
+                f,
+                "ltxtquery word at byte {} is {} characters long, the maximum is {}",
+                offset, length, MAX_LABEL_LENGTH
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+enum Token {
+    Word(Word),
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+/// Splits the input into tokens the way the ltree extension does, which also
+/// checks that operands and operators alternate and parentheses balance.
+fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
+    enum State {
+        Operand,
+        InOperand(usize, Word),
+        Operator,
+    }
+
+    let mut tokens = Vec::new();
+    let mut state = State::Operand;
+    let mut depth = 0usize;
+    let mut chars = s.char_indices().peekable();
+
+    loop {
+        let (offset, c) = match chars.peek() {
+            Some(&(offset, c)) => (offset, Some(c)),
+            None => (s.len(), None),
+        };
+        let unexpected = |expected| ParseError::Unexpected {
+            offset,
+            found: c,
+            expected,
+        };
+        state = match (state, c) {
+            (State::Operand, Some('!')) => {
+                tokens.push(Token::Not);
+                State::Operand
+            }
+            (State::Operand, Some('(')) => {
+                depth += 1;
+                tokens.push(Token::Open);
+                State::Operand
+            }
+            (State::Operand, Some(c)) if is_label_char(c) => State::InOperand(offset, Word::new(c)),
+            (State::Operand, Some(' ' | '\t' | '\n' | '\r' | '\x0b' | '\x0c')) => State::Operand,
+            (State::Operand, _) => return Err(unexpected(Expected::Operand)),
+            (State::InOperand(start, mut word), c) => {
+                match c {
+                    Some(c) if is_label_char(c) => {
+                        if word.prefix || word.case_insensitive || word.word {
+                            return Err(unexpected(Expected::Modifier));
+                        }
+                        word.label.push(c);
+                    }
+                    Some('%') => word.word = true,
+                    Some('@') => word.case_insensitive = true,
+                    Some('*') => word.prefix = true,
+                    _ => {
+                        let length = word.label.chars().count();
+                        if length > MAX_LABEL_LENGTH {
+                            return Err(ParseError::WordTooLong {
+                                offset: start,
+                                length,
+                            });
+                        }
+                        tokens.push(Token::Word(word));
+                        // Leave the character for the operator state.
+                        state = State::Operator;
+                        continue;
+                    }
+                }
+                State::InOperand(start, word)
+            }
+            (State::Operator, Some('&')) => {
+                tokens.push(Token::And);
+                State::Operand
+            }
+            (State::Operator, Some('|')) => {
+                tokens.push(Token::Or);
+                State::Operand
+            }
+            (State::Operator, Some(')')) if depth > 0 => {
+                depth -= 1;
+                tokens.push(Token::Close);
+                State::Operator
+            }
+            (State::Operator, None) if depth > 0 => return Err(unexpected(Expected::CloseParen)),
+            (State::Operator, None) => return Ok(tokens),
+            // Unlike before an operand, only spaces are skipped here.
+            (State::Operator, Some(' ')) => State::Operator,
+            (State::Operator, _) => return Err(unexpected(Expected::Operator)),
+        };
+        chars.next();
+    }
+}
+
+type Tokens = std::iter::Peekable<std::vec::IntoIter<Token>>;
+
+// The token stream is known to be well formed, so parsing cannot fail.
+
+fn parse_or(tokens: &mut Tokens) -> Txt {
+    let mut left = parse_and(tokens);
+    while let Some(Token::Or) = tokens.peek() {
+        tokens.next();
+        left = left | parse_and(tokens);
+    }
+    left
+}
+
+fn parse_and(tokens: &mut Tokens) -> Txt {
+    let mut left = parse_not(tokens);
+    while let Some(Token::And) = tokens.peek() {
+        tokens.next();
+        left = left & parse_not(tokens);
+    }
+    left
+}
+
+fn parse_not(tokens: &mut Tokens) -> Txt {
+    match tokens.next() {
+        Some(Token::Not) => !parse_not(tokens),
+        Some(Token::Open) => {
+            let inner = parse_or(tokens);
+            tokens.next();
+            inner
+        }
+        Some(Token::Word(word)) => Txt::Word(word),
+        _ => unreachable!("ltxtquery tokens out of order"),
+    }
+}
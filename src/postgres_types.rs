@@ -0,0 +1,45 @@
+//! `ToSql` and `FromSql` for the [`postgres-types`](https://docs.rs/postgres-types)
+//! crate used by `postgres` and `tokio-postgres`, in the same binary format as
+//! the Diesel impls.
+//!
+//! The ltree extension's types have no fixed OIDs, so these accept any type
+//! with the matching name.
+use std::error::Error;
+
+use bytes::{BufMut, BytesMut};
+use postgres_types::{to_sql_checked, FromSql, IsNull, ToSql, Type};
+
+use crate::values::{decode_binary, encode_binary, Lquery, Ltree, Ltxtquery};
+
+macro_rules! postgres_types_impls {
+    ($($ty:ident => $name:literal),*) => {$(
+        impl ToSql for $ty {
+            fn to_sql(
+                &self,
+                _: &Type,
+                out: &mut BytesMut,
+            ) -> Result<IsNull, Box<dyn Error + Sync + Send>> {
+                encode_binary(&self.0, &mut out.writer())?;
+                Ok(IsNull::No)
+            }
+
+            fn accepts(ty: &Type) -> bool {
+                ty.name() == $name
+            }
+
+            to_sql_checked!();
+        }
+
+        impl<'a> FromSql<'a> for $ty {
+            fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn Error + Sync + Send>> {
+                Ok($ty(decode_binary(raw)?.to_string()))
+            }
+
+            fn accepts(ty: &Type) -> bool {
+                ty.name() == $name
+            }
+        }
+    )*};
+}
+
+postgres_types_impls!(Ltree => "ltree", Lquery => "lquery", Ltxtquery => "ltxtquery");
@@ -0,0 +1,261 @@
+//! Creating the ltree extension and the indexes ltree columns need, and
+//! finding the columns that lack them.
+//!
+//! The statements built here run with `execute` like any other Diesel
+//! statement, and print with `debug_query` for pasting into a migration.
+//!
+//! Without a GiST index, `@>`, `<@`, `~` and `@` scan the whole table.
+//! [`unindexed_columns`] lists the ltree and `ltree[]` columns with no index of
+//! a given method, so a test can check that none were forgotten.
+//!
+//! ```no_run
+//! # use diesel::prelude::*;
+//! # use diesel_ltree::schema::{self, IndexMethod};
+//! # diesel::table! { use diesel_ltree::sql_types::Ltree; use diesel::sql_types::*;
+//! #     taxonomy (id) { id -> Int4, path -> Ltree, } }
+//! # fn run(conn: &mut PgConnection) -> QueryResult<()> {
+//! schema::create_extension().execute(conn)?;
+//! schema::create_index(
+//!     "taxonomy_path_gist",
+//!     taxonomy::table,
+//!     taxonomy::path,
+//!     IndexMethod::Gist,
+//! )
+//! .siglen(100)
+//! .execute(conn)?;
+//! assert!(schema::unindexed_columns(conn, IndexMethod::Gist)?
+//!     .iter()
+//!     .all(|c| c.table != "taxonomy"));
+//! # Ok(())
+//! # }
+//! ```
+use std::marker::PhantomData;
+
+use diesel::connection::LoadConnection;
+use diesel::pg::Pg;
+use diesel::query_builder::{AstPass, QueryFragment, QueryId};
+use diesel::query_dsl::RunQueryDsl;
+use diesel::result::QueryResult;
+use diesel::sql_types::{Array, Bool, Nullable, Text};
+use diesel::{Column, QueryableByName, Table};
+
+use crate::sql_types::Ltree;
+
+/// `CREATE EXTENSION IF NOT EXISTS ltree`.
+#[derive(Debug, Clone, Copy)]
+pub struct CreateExtension;
+
+/// Builds `CREATE EXTENSION IF NOT EXISTS ltree`.
+pub fn create_extension() -> CreateExtension {
+    CreateExtension
+}
+
+impl QueryFragment<Pg> for CreateExtension {
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql("CREATE EXTENSION IF NOT EXISTS ltree");
+        Ok(())
+    }
+}
+
+impl QueryId for CreateExtension {
+    type QueryId = Self;
+
+    const HAS_STATIC_QUERY_ID: bool = true;
+}
+
+impl<Conn> RunQueryDsl<Conn> for CreateExtension {}
+
+/// The index access methods that support ltree columns.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum IndexMethod {
+    /// Supports `@>`, `<@`, `~`, `@` and their array forms, as well as the
+    /// comparison operators. Uses `gist_ltree_ops` for ltree columns and
+    /// `gist__ltree_ops` for `ltree[]` columns.
+    Gist,
+    /// Supports the comparison operators and `ORDER BY`.
+    Btree,
+    /// Supports only `=`. Requires PostgreSQL 16 or later (ltree 1.3).
+    Hash,
+}
+
+impl IndexMethod {
+    /// The method's name, as in `USING` and `pg_am.amname`.
+    pub fn name(self) -> &'static str {
+        match self {
+            IndexMethod::Gist => "gist",
+            IndexMethod::Btree => "btree",
+            IndexMethod::Hash => "hash",
+        }
+    }
+}
+
+/// The SQL types that can be indexed by [`create_index`], with the GiST
+/// operator class for each.
+pub trait IndexedType {
+    #[doc(hidden)]
+    const GIST_OPCLASS: &'static str;
+}
+
+impl IndexedType for Ltree {
+    const GIST_OPCLASS: &'static str = "gist_ltree_ops";
+}
+
+impl IndexedType for Nullable<Ltree> {
+    const GIST_OPCLASS: &'static str = "gist_ltree_ops";
+}
+
+impl IndexedType for Array<Ltree> {
+    const GIST_OPCLASS: &'static str = "gist__ltree_ops";
+}
+
+impl IndexedType for Nullable<Array<Ltree>> {
+    const GIST_OPCLASS: &'static str = "gist__ltree_ops";
+}
+
+/// `CREATE INDEX IF NOT EXISTS name ON table USING method (column ...)`.
+#[derive(Debug, Clone)]
+pub struct CreateIndex<F, C> {
+    table: F,
+    column: PhantomData<C>,
+    name: String,
+    method: IndexMethod,
+    siglen: Option<u16>,
+}
+
+/// Builds `CREATE INDEX IF NOT EXISTS name ON table USING method (column)`,
+/// which does nothing if an index called `name` already exists in the
+/// table's schema.
+pub fn create_index<T, C>(
+    name: &str,
+    table: T,
+    _column: C,
+    method: IndexMethod,
+) -> CreateIndex<T::FromClause, C>
+where
+    T: Table,
+    C: Column<Table = T>,
+    C::SqlType: IndexedType,
+{
+    CreateIndex {
+        table: table.from_clause(),
+        column: PhantomData,
+        name: name.to_string(),
+        method,
+        siglen: None,
+    }
+}
+
+impl<F, C> CreateIndex<F, C> {
+    /// Sets the signature length, in bytes, of a GiST index.
+    ///
+    /// Longer signatures make the index larger but more selective. Postgres
+    /// accepts a positive multiple of 4 up to 2024, defaulting to 8 for ltree
+    /// columns and 28 for `ltree[]` columns; other lengths make the statement
+    /// fail to build. Requires PostgreSQL 13 or later.
+    pub fn siglen(mut self, siglen: u16) -> Self {
+        self.siglen = Some(siglen);
+        self
+    }
+}
+
+impl<F, C> QueryFragment<Pg> for CreateIndex<F, C>
+where
+    F: QueryFragment<Pg>,
+    C: Column,
+    C::SqlType: IndexedType,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql("CREATE INDEX IF NOT EXISTS ");
+        out.push_identifier(&self.name)?;
+        out.push_sql(" ON ");
+        self.table.walk_ast(out.reborrow())?;
+        out.push_sql(" USING ");
+        out.push_sql(self.method.name());
+        out.push_sql(" (");
+        out.push_identifier(C::NAME)?;
+        match (self.method, self.siglen) {
+            (IndexMethod::Gist, siglen) => {
+                out.push_sql(" ");
+                out.push_sql(<C::SqlType as IndexedType>::GIST_OPCLASS);
+                if let Some(siglen) = siglen {
+                    if siglen == 0 || siglen % 4 != 0 || siglen > 2024 {
+                        return Err(diesel::result::Error::QueryBuilderError(
+                            "siglen must be a positive multiple of 4 up to 2024".into(),
+                        ));
+                    }
+                    // DDL cannot take bind parameters.
+                    out.push_sql(&format!("(siglen = {})", siglen));
+                }
+            }
+            (_, Some(_)) => {
+                return Err(diesel::result::Error::QueryBuilderError(
+                    "siglen only applies to GiST indexes".into(),
+                ))
+            }
+            (_, None) => {}
+        }
+        out.push_sql(")");
+        Ok(())
+    }
+}
+
+impl<F, C> QueryId for CreateIndex<F, C> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<F, C, Conn> RunQueryDsl<Conn> for CreateIndex<F, C> {}
+
+/// An ltree or `ltree[]` column reported by [`unindexed_columns`].
+#[derive(Debug, PartialEq, Eq, Clone, QueryableByName)]
+pub struct UnindexedColumn {
+    #[diesel(sql_type = Text)]
+    pub schema: String,
+    #[diesel(sql_type = Text)]
+    pub table: String,
+    #[diesel(sql_type = Text)]
+    pub column: String,
+    /// Whether the column is an `ltree[]`.
+    #[diesel(sql_type = Bool)]
+    pub is_array: bool,
+}
+
+/// Lists the ltree and `ltree[]` columns of every table, outside the system
+/// schemas, that are not a key of any index using `method`, ordered by
+/// schema, table and column.
+///
+/// Indexes are read from `pg_indexes`, so only those on tables the current
+/// user can see are considered. A column that is one of the keys of a
+/// multicolumn or partial index counts as indexed.
+pub fn unindexed_columns<Conn>(
+    conn: &mut Conn,
+    method: IndexMethod,
+) -> QueryResult<Vec<UnindexedColumn>>
+where
+    Conn: LoadConnection<Backend = Pg>,
+{
+    diesel::sql_query(
+        "SELECT n.nspname::text AS \"schema\", t.relname::text AS \"table\", \
+                a.attname::text AS \"column\", ty.typname = '_ltree' AS is_array \
+         FROM pg_attribute a \
+         JOIN pg_class t ON t.oid = a.attrelid \
+         JOIN pg_namespace n ON n.oid = t.relnamespace \
+         JOIN pg_type ty ON ty.oid = a.atttypid \
+         WHERE ty.typname IN ('ltree', '_ltree') \
+           AND t.relkind IN ('r', 'p') \
+           AND a.attnum > 0 AND NOT a.attisdropped \
+           AND n.nspname NOT IN ('pg_catalog', 'information_schema') \
+           AND NOT EXISTS ( \
+             SELECT 1 FROM pg_indexes i \
+             JOIN pg_index x \
+               ON x.indexrelid = format('%I.%I', i.schemaname, i.indexname)::regclass \
+             JOIN pg_class ic ON ic.oid = x.indexrelid \
+             JOIN pg_am am ON am.oid = ic.relam \
+             WHERE i.schemaname = n.nspname AND i.tablename = t.relname \
+               AND am.amname = $1 AND a.attnum = ANY(x.indkey)) \
+         ORDER BY 1, 2, 3",
+    )
+    .bind::<Text, _>(method.name())
+    .load(conn)
+}
@@ -0,0 +1,276 @@
+//! Running tree queries on SQLite, for local development and tests.
+//!
+//! SQLite has no ltree extension, so ltree, lquery and ltxtquery values are
+//! stored as TEXT and [`register_functions`] registers Rust implementations of
+//! the common functions on a connection:
+//!
+//! - `subltree`, `subpath` (both forms), `nlevel` and `lca`, which takes two
+//!   to eight paths since SQLite has no arrays;
+//! - `ltree_index` (both forms), since `index` is a keyword in SQLite. Call it
+//!   with [`index()`] and [`index_of()`] from this module rather than the crate
+//!   root;
+//! - `text2ltree`, `ltree2text` and `lquery`;
+//! - `ltree_contains` and `ltree_matches`, which `@>`, `<@` and `~` from
+//!   [`LtreeExtensions`](crate::LtreeExtensions) and
+//!   [`LqueryExtensions`](crate::LqueryExtensions) are written as.
+//!
+//! It also registers an `ltree` collation. Declare path columns as
+//! `TEXT COLLATE ltree` so that comparing and ordering them agrees with
+//! Postgres.
+//!
+//! The functions use the client-side equivalents on [`Ltree`] and
+//! [`Lquery`], so they return `NULL` where Postgres would raise an error. The
+//! other operators and functions, and the subtree and adjacency-list
+//! helpers, remain Postgres-only.
+//!
+//! ```
+//! # use diesel::prelude::*;
+//! # use diesel::sqlite::SqliteConnection;
+//! use diesel_ltree::{nlevel, text2ltree, LtreeExtensions};
+//!
+//! let mut conn = SqliteConnection::establish(":memory:").unwrap();
+//! diesel_ltree::sqlite::register_functions(&mut conn).unwrap();
+//!
+//! let (levels, below) = diesel::select((
+//!     nlevel(text2ltree("root.eukaryota.plantae")),
+//!     text2ltree("root.eukaryota.plantae").contained_by(text2ltree("root")),
+//! ))
+//! .get_result::<(i32, bool)>(&mut conn)
+//! .unwrap();
+//! assert_eq!((levels, below), (3, true));
+//! ```
+use std::cmp::Ordering;
+
+use diesel::deserialize::{self, FromSql};
+use diesel::expression::Expression;
+use diesel::query_builder::{AstPass, QueryFragment};
+use diesel::result::QueryResult;
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{Bool, Int4, Nullable, Text};
+use diesel::sqlite::{Sqlite, SqliteConnection, SqliteValue};
+
+use crate::dsl::predicates::{ContainedBy, Contains, Matches};
+use crate::dsl::LtreeOrNullableLtree;
+use crate::lquery::Query;
+use crate::sql_types;
+use crate::values::{Lquery, Ltree, Ltxtquery};
+
+macro_rules! sqlite_impls {
+    ($($ty:ident),*) => {$(
+        impl ToSql<sql_types::$ty, Sqlite> for $ty {
+            fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Sqlite>) -> serialize::Result {
+                out.set_value(self.0.as_str());
+                Ok(IsNull::No)
+            }
+        }
+
+        impl FromSql<sql_types::$ty, Sqlite> for $ty {
+            fn from_sql(value: SqliteValue<'_, '_, '_>) -> deserialize::Result<Self> {
+                <String as FromSql<Text, Sqlite>>::from_sql(value).map($ty)
+            }
+        }
+    )*};
+}
+
+sqlite_impls!(Ltree, Lquery, Ltxtquery);
+
+define_sql_function! {
+    /// [`crate::index()`] for SQLite, calling the `ltree_index` function
+    /// registered by [`register_functions`].
+    #[sql_name = "ltree_index"]
+    fn index(a: sql_types::Ltree, b: sql_types::Ltree, offset: Int4) -> Int4;
+}
+define_sql_function! {
+    /// [`crate::index_of()`] for SQLite, calling the `ltree_index` function
+    /// registered by [`register_functions`].
+    #[sql_name = "ltree_index"]
+    fn index_of(a: sql_types::Ltree, b: sql_types::Ltree) -> Int4;
+}
+
+type NullableLtree = Nullable<sql_types::Ltree>;
+type NullableLquery = Nullable<sql_types::Lquery>;
+type NullableInt4 = Nullable<Int4>;
+
+/// Registers the functions and collation described in the [module
+/// documentation](self) on `conn`. This must be called on every connection
+/// before it runs a query using them.
+pub fn register_functions(conn: &mut SqliteConnection) -> QueryResult<()> {
+    conn.register_sql_function::<(NullableLtree, NullableInt4, NullableInt4), NullableLtree, _, _, _>(
+        "subltree",
+        true,
+        |(path, start, end): (Option<Ltree>, Option<i32>, Option<i32>)| {
+            path?.subltree(start?, end?)
+        },
+    )?;
+    conn.register_sql_function::<(NullableLtree, NullableInt4, NullableInt4), NullableLtree, _, _, _>(
+        "subpath",
+        true,
+        |(path, offset, len): (Option<Ltree>, Option<i32>, Option<i32>)| {
+            path?.subpath(offset?, len?)
+        },
+    )?;
+    conn.register_sql_function::<(NullableLtree, NullableInt4), NullableLtree, _, _, _>(
+        "subpath",
+        true,
+        |(path, offset): (Option<Ltree>, Option<i32>)| path?.subpath(offset?, i32::MAX),
+    )?;
+    conn.register_sql_function::<(NullableLtree,), NullableInt4, _, _, _>(
+        "nlevel",
+        true,
+        |(path,): (Option<Ltree>,)| Some(path?.nlevel() as i32),
+    )?;
+    // `index` returns -1 rather than `NULL` when `b` is not found.
+    conn.register_sql_function::<(NullableLtree, NullableLtree, NullableInt4), NullableInt4, _, _, _>(
+        "ltree_index",
+        true,
+        |(a, b, offset): (Option<Ltree>, Option<Ltree>, Option<i32>)| {
+            Some(a?.index_of(&b?, offset?).map_or(-1, |i| i as i32))
+        },
+    )?;
+    conn.register_sql_function::<(NullableLtree, NullableLtree), NullableInt4, _, _, _>(
+        "ltree_index",
+        true,
+        |(a, b): (Option<Ltree>, Option<Ltree>)| Some(a?.index_of(&b?, 0).map_or(-1, |i| i as i32)),
+    )?;
+    register_lca(conn)?;
+
+    conn.register_sql_function::<(Nullable<Text>,), NullableLtree, _, _, _>(
+        "text2ltree",
+        true,
+        |(text,): (Option<String>,)| Ltree::try_from(text?).ok(),
+    )?;
+    conn.register_sql_function::<(NullableLtree,), Nullable<Text>, _, _, _>(
+        "ltree2text",
+        true,
+        |(path,): (Option<Ltree>,)| path.map(|path| path.0),
+    )?;
+    conn.register_sql_function::<(Nullable<Text>,), NullableLquery, _, _, _>(
+        "lquery",
+        true,
+        |(text,): (Option<String>,)| {
+            let text = text?;
+            Query::parse(&text).ok().map(|_| Lquery(text))
+        },
+    )?;
+
+    conn.register_sql_function::<(NullableLtree, NullableLtree), Nullable<Bool>, _, _, _>(
+        "ltree_contains",
+        true,
+        |(ancestor, path): (Option<Ltree>, Option<Ltree>)| Some(ancestor?.is_ancestor_of(&path?)),
+    )?;
+    conn.register_sql_function::<(NullableLtree, NullableLquery), Nullable<Bool>, _, _, _>(
+        "ltree_matches",
+        true,
+        |(path, query): (Option<Ltree>, Option<Lquery>)| Some(query?.is_match(&path?)),
+    )?;
+
+    // Diesel calls the closure with SQLite's two arguments in reverse order.
+    conn.register_collation("ltree", |rhs, lhs| collate(lhs, rhs))
+}
+
+// The `ltree` collation, comparing `lhs` with `rhs` as SQLite's collation
+// contract expects.
+fn collate(lhs: &str, rhs: &str) -> Ordering {
+    Ltree(lhs.to_string()).cmp(&Ltree(rhs.to_string()))
+}
+
+// Registers `lca` for each number of arguments from two to eight.
+fn register_lca(conn: &mut SqliteConnection) -> QueryResult<()> {
+    macro_rules! replace {
+        ($_arg:ident $($ty:tt)*) => { $($ty)* };
+    }
+    macro_rules! register {
+        ($($arg:ident),*) => {
+            conn.register_sql_function::<($(replace!($arg NullableLtree),)*), NullableLtree, _, _, _>(
+                "lca",
+                true,
+                |($($arg,)*): ($(replace!($arg Option<Ltree>),)*)| Ltree::lca(&[$($arg?,)*]),
+            )?;
+        };
+    }
+
+    register!(a, b);
+    register!(a, b, c);
+    register!(a, b, c, d);
+    register!(a, b, c, d, e);
+    register!(a, b, c, d, e, f);
+    register!(a, b, c, d, e, f, g);
+    register!(a, b, c, d, e, f, g, h);
+    Ok(())
+}
+
+// `@>`, `<@` and `~` as calls to the functions registered above.
+
+impl<L, R> QueryFragment<Sqlite> for Contains<L, R>
+where
+    L: Expression + QueryFragment<Sqlite>,
+    R: QueryFragment<Sqlite>,
+    L::SqlType: LtreeOrNullableLtree,
+{
+    fn walk_ast<'b>(&'b self, out: AstPass<'_, 'b, Sqlite>) -> QueryResult<()> {
+        walk_call("ltree_contains", &self.left, &self.right, out)
+    }
+}
+
+impl<L, R> QueryFragment<Sqlite> for ContainedBy<L, R>
+where
+    L: Expression + QueryFragment<Sqlite>,
+    R: QueryFragment<Sqlite>,
+    L::SqlType: LtreeOrNullableLtree,
+{
+    fn walk_ast<'b>(&'b self, out: AstPass<'_, 'b, Sqlite>) -> QueryResult<()> {
+        walk_call("ltree_contains", &self.right, &self.left, out)
+    }
+}
+
+impl<L, R> QueryFragment<Sqlite> for Matches<L, R>
+where
+    L: Expression + QueryFragment<Sqlite>,
+    R: QueryFragment<Sqlite>,
+    L::SqlType: MatchOperand,
+{
+    fn walk_ast<'b>(&'b self, out: AstPass<'_, 'b, Sqlite>) -> QueryResult<()> {
+        if <L::SqlType as MatchOperand>::IS_LTREE {
+            walk_call("ltree_matches", &self.left, &self.right, out)
+        } else {
+            walk_call("ltree_matches", &self.right, &self.left, out)
+        }
+    }
+}
+
+// The SQL types on the left of `~`, which may be either the path or the
+// query.
+trait MatchOperand {
+    const IS_LTREE: bool;
+}
+
+impl MatchOperand for sql_types::Ltree {
+    const IS_LTREE: bool = true;
+}
+
+impl MatchOperand for NullableLtree {
+    const IS_LTREE: bool = true;
+}
+
+impl MatchOperand for sql_types::Lquery {
+    const IS_LTREE: bool = false;
+}
+
+impl MatchOperand for NullableLquery {
+    const IS_LTREE: bool = false;
+}
+
+fn walk_call<'b>(
+    name: &str,
+    a: &'b dyn QueryFragment<Sqlite>,
+    b: &'b dyn QueryFragment<Sqlite>,
+    mut out: AstPass<'_, 'b, Sqlite>,
+) -> QueryResult<()> {
+    out.push_sql(name);
+    out.push_sql("(");
+    a.walk_ast(out.reborrow())?;
+    out.push_sql(", ");
+    b.walk_ast(out.reborrow())?;
+    out.push_sql(")");
+    Ok(())
+}
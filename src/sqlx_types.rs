@@ -0,0 +1,52 @@
+//! `Type`, `Encode` and `Decode` for [`sqlx`](https://docs.rs/sqlx)'s Postgres
+//! driver, in the same binary format as the Diesel impls.
+//!
+//! The ltree extension's types have no fixed OIDs, so sqlx looks them up by
+//! name the first time each is used on a connection.
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{
+    PgArgumentBuffer, PgHasArrayType, PgTypeInfo, PgValueFormat, PgValueRef, Postgres,
+};
+use sqlx::{Decode, Encode, Type};
+
+use crate::values::{decode_binary, encode_binary, Lquery, Ltree, Ltxtquery};
+
+macro_rules! sqlx_impls {
+    ($($ty:ident => $name:literal, $array_name:literal),*) => {$(
+        impl Type<Postgres> for $ty {
+            fn type_info() -> PgTypeInfo {
+                PgTypeInfo::with_name($name)
+            }
+        }
+
+        impl PgHasArrayType for $ty {
+            fn array_type_info() -> PgTypeInfo {
+                PgTypeInfo::with_name($array_name)
+            }
+        }
+
+        impl Encode<'_, Postgres> for $ty {
+            fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+                encode_binary(&self.0, &mut **buf)?;
+                Ok(IsNull::No)
+            }
+        }
+
+        impl<'r> Decode<'r, Postgres> for $ty {
+            fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+                let text = match value.format() {
+                    PgValueFormat::Binary => decode_binary(value.as_bytes()?)?,
+                    PgValueFormat::Text => value.as_str()?,
+                };
+                Ok($ty(text.to_string()))
+            }
+        }
+    )*};
+}
+
+sqlx_impls!(
+    Ltree => "ltree", "_ltree",
+    Lquery => "lquery", "_lquery",
+    Ltxtquery => "ltxtquery", "_ltxtquery"
+);
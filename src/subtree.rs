@@ -0,0 +1,532 @@
+//! Helpers for rewriting whole subtrees of a table with an ltree column.
+//!
+//! Each helper issues its statements on the connection it is given, so they
+//! take part in whatever transaction the caller has open.
+
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
+use diesel::connection::{Connection, LoadConnection};
+use diesel::dsl::AsExprOf;
+use diesel::expression::Expression;
+use diesel::pg::Pg;
+use diesel::query_builder::{
+    AsChangeset, AsQuery, AstPass, DeleteStatement, IntoUpdateTarget, Query, QueryFragment,
+    QueryId, UpdateStatement,
+};
+use diesel::query_dsl::methods::{ExecuteDsl, FilterDsl, SelectDsl};
+use diesel::query_dsl::RunQueryDsl;
+use diesel::result::{OptionalExtension, QueryResult};
+use diesel::{BoolExpressionMethods, Column, ExpressionMethods, IntoSql, Table};
+
+use crate::dsl::predicates::{Concat, ContainedBy};
+use crate::functions::{nlevel, subpath, subpath_from};
+use crate::sql_types;
+use crate::values::{Ltree, MAX_LEVELS};
+use crate::LtreeExtensions;
+
+/// Why a subtree operation was refused or failed.
+#[derive(Debug, PartialEq)]
+pub enum SubtreeError {
+    /// The destination lies strictly inside the subtree being moved.
+    IntoOwnSubtree { from: Ltree, to: Ltree },
+    /// There is no node at the root being deleted, so its descendants were
+    /// not re-parented.
+    MissingRoot { root: Ltree },
+    /// Re-parenting would move a descendant onto a path that already exists.
+    PathTaken { path: Ltree },
+    /// The database reported an error.
+    Database(diesel::result::Error),
+}
+
+impl fmt::Display for SubtreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubtreeError::IntoOwnSubtree { from, to } => {
+                write!(f, "cannot move `{}` to `{}`, inside itself", from, to)
+            }
+            SubtreeError::MissingRoot { root } => write!(f, "there is no node at `{}`", root),
+            SubtreeError::PathTaken { path } => {
+                write!(
+                    f,
+                    "cannot move a descendant to `{}`, which already exists",
+                    path
+                )
+            }
+            SubtreeError::Database(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for SubtreeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SubtreeError::IntoOwnSubtree { .. }
+            | SubtreeError::MissingRoot { .. }
+            | SubtreeError::PathTaken { .. } => None,
+            SubtreeError::Database(e) => Some(e),
+        }
+    }
+}
+
+impl From<diesel::result::Error> for SubtreeError {
+    fn from(e: diesel::result::Error) -> Self {
+        SubtreeError::Database(e)
+    }
+}
+
+type Path = AsExprOf<Ltree, sql_types::Ltree>;
+
+// `column <@ from`
+pub(crate) type MoveFilter<C> = ContainedBy<C, Path>;
+
+// `to || subpath(column || '_', depth, -1)`
+pub(crate) type MovedPath<C> = Concat<Path, subpath<Concat<C, Path>, i32, i32>>;
+
+pub(crate) type MoveSet<C> = diesel::dsl::Eq<C, MovedPath<C>>;
+
+fn subtree_filter<C>(column: C, root: &Ltree) -> MoveFilter<C>
+where
+    C: Column<SqlType = sql_types::Ltree>,
+{
+    column.contained_by(root.clone())
+}
+
+fn moved_path<C>(column: C, from: &Ltree, to: &Ltree) -> MovedPath<C>
+where
+    C: Column<SqlType = sql_types::Ltree>,
+{
+    // `subpath` refuses an offset at the end of the path, so a label is
+    // appended and cut off again to give the node itself an empty tail.
+    let padded = column.concat(Ltree("_".to_string()));
+    let tail = subpath(padded, from.nlevel() as i32, -1);
+    to.clone().into_sql::<sql_types::Ltree>().concat(tail)
+}
+
+/// Moves the node at `from`, along with all of its descendants, so that it
+/// sits at `to`, returning the number of rows updated.
+///
+/// This is a single `UPDATE` replacing the leading `from` of `column` with
+/// `to`, on the node itself and on its descendants. Moving a node somewhere
+/// below itself is refused with [`SubtreeError::IntoOwnSubtree`].
+///
+/// ```no_run
+/// # use diesel::prelude::*;
+/// # use diesel_ltree::Ltree;
+/// # diesel::table! { use diesel_ltree::sql_types::Ltree; use diesel::sql_types::*;
+/// #     taxonomy (id) { id -> Int4, path -> Ltree, } }
+/// # fn run(conn: &mut PgConnection) -> Result<(), diesel_ltree::subtree::SubtreeError> {
+/// let moved = diesel_ltree::subtree::move_subtree(
+///     conn,
+///     taxonomy::table,
+///     taxonomy::path,
+///     &Ltree("root.eukaryota.plantae".to_string()),
+///     &Ltree("root.archaea.plantae".to_string()),
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn move_subtree<Conn, T, C, F, W, S>(
+    conn: &mut Conn,
+    table: T,
+    column: C,
+    from: &Ltree,
+    to: &Ltree,
+) -> Result<usize, SubtreeError>
+where
+    Conn: Connection<Backend = Pg>,
+    T: FilterDsl<MoveFilter<C>, Output = F>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    F: IntoUpdateTarget<WhereClause = W>,
+    MoveSet<C>: AsChangeset<Target = F::Table, Changeset = S>,
+    UpdateStatement<F::Table, W, S>: AsQuery + ExecuteDsl<Conn>,
+{
+    Ok(move_statement(table, column, from, to)?.execute(conn)?)
+}
+
+// Shared with the async `move_subtree`.
+pub(crate) fn move_statement<T, C, F, W, S>(
+    table: T,
+    column: C,
+    from: &Ltree,
+    to: &Ltree,
+) -> Result<UpdateStatement<F::Table, W, S>, SubtreeError>
+where
+    T: FilterDsl<MoveFilter<C>, Output = F>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    F: IntoUpdateTarget<WhereClause = W>,
+    MoveSet<C>: AsChangeset<Target = F::Table, Changeset = S>,
+    UpdateStatement<F::Table, W, S>: AsQuery,
+{
+    if to != from && to.is_descendant_of(from) {
+        return Err(SubtreeError::IntoOwnSubtree {
+            from: from.clone(),
+            to: to.clone(),
+        });
+    }
+
+    let set: MoveSet<C> = column.eq(moved_path(column, from, to));
+    Ok(diesel::update(table.filter(subtree_filter(column, from))).set(set))
+}
+
+/// A tuple of columns from one table, named in the column list of the
+/// `INSERT` made by [`copy_subtree`].
+pub trait InsertColumns: Expression {
+    type Table: Table;
+
+    #[doc(hidden)]
+    fn walk_names(out: &mut AstPass<'_, '_, Pg>) -> QueryResult<()>;
+}
+
+macro_rules! insert_columns {
+    ($($first:ident $(, $rest:ident)*;)+) => {$(
+        impl<$first, $($rest),*> InsertColumns for ($first, $($rest,)*)
+        where
+            $first: Column,
+            $($rest: Column<Table = $first::Table>,)*
+            Self: Expression,
+        {
+            type Table = $first::Table;
+
+            fn walk_names(out: &mut AstPass<'_, '_, Pg>) -> QueryResult<()> {
+                out.push_identifier($first::NAME)?;
+                $(
+                    out.push_sql(", ");
+                    out.push_identifier($rest::NAME)?;
+                )*
+                Ok(())
+            }
+        }
+    )+};
+}
+
+insert_columns! {
+    A;
+    A, B;
+    A, B, C;
+    A, B, C, D;
+    A, B, C, D, E;
+    A, B, C, D, E, F;
+    A, B, C, D, E, F, G;
+    A, B, C, D, E, F, G, H;
+    A, B, C, D, E, F, G, H, I;
+    A, B, C, D, E, F, G, H, I, J;
+    A, B, C, D, E, F, G, H, I, J, K;
+    A, B, C, D, E, F, G, H, I, J, K, L;
+}
+
+// `INSERT INTO table (columns) query`, which Diesel can build but whose type
+// cannot be named outside of it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InsertFromSelect<Into, Cols, Q> {
+    into: Into,
+    columns: PhantomData<Cols>,
+    query: Q,
+}
+
+impl<Into, Cols, Q> QueryFragment<Pg> for InsertFromSelect<Into, Cols, Q>
+where
+    Into: QueryFragment<Pg>,
+    Cols: InsertColumns,
+    Q: QueryFragment<Pg>,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql("INSERT INTO ");
+        self.into.walk_ast(out.reborrow())?;
+        out.push_sql(" (");
+        Cols::walk_names(&mut out)?;
+        out.push_sql(") ");
+        self.query.walk_ast(out.reborrow())
+    }
+}
+
+impl<Into, Cols, Q> QueryId for InsertFromSelect<Into, Cols, Q> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<Into, Cols, Q, Conn> RunQueryDsl<Conn> for InsertFromSelect<Into, Cols, Q> {}
+
+/// Copies the node at `from`, along with all of its descendants, so that the
+/// copy sits at `to`, returning the number of rows inserted.
+///
+/// This is a single `INSERT INTO table (columns) SELECT ...` over the rows of
+/// the subtree. `remap` is given the rewritten path, the same expression
+/// [`move_subtree`] sets the column to, and returns the select clause for
+/// `columns`. Columns left out of `columns`, such as a serial primary key,
+/// take their defaults.
+///
+/// ```no_run
+/// # use diesel::prelude::*;
+/// # use diesel_ltree::Ltree;
+/// # diesel::table! { use diesel_ltree::sql_types::Ltree; use diesel::sql_types::*;
+/// #     taxonomy (id) { id -> Int4, path -> Ltree, name -> Text, } }
+/// # fn run(conn: &mut PgConnection) -> Result<(), diesel_ltree::subtree::SubtreeError> {
+/// let copied = diesel_ltree::subtree::copy_subtree(
+///     conn,
+///     taxonomy::table,
+///     taxonomy::path,
+///     &Ltree("templates.default".to_string()),
+///     &Ltree("projects.new".to_string()),
+///     (taxonomy::path, taxonomy::name),
+///     |path| (path, taxonomy::name),
+/// )?;
+/// # Ok(())
+/// # }
+/// ```
+#[allow(clippy::too_many_arguments)]
+pub fn copy_subtree<Conn, T, C, Cols, R, Sel, F, Q>(
+    conn: &mut Conn,
+    table: T,
+    column: C,
+    from: &Ltree,
+    to: &Ltree,
+    _columns: Cols,
+    remap: R,
+) -> Result<usize, SubtreeError>
+where
+    Conn: Connection<Backend = Pg>,
+    T: Table + Copy + FilterDsl<MoveFilter<C>, Output = F>,
+    T::FromClause: QueryFragment<Pg>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    Cols: InsertColumns<Table = T>,
+    R: FnOnce(MovedPath<C>) -> Sel,
+    Sel: Expression,
+    F: SelectDsl<Sel, Output = Q>,
+    Q: Query<SqlType = Cols::SqlType> + QueryFragment<Pg>,
+{
+    Ok(copy_statement::<_, _, Cols, _, _, _, _>(table, column, from, to, remap).execute(conn)?)
+}
+
+// Shared with the async `copy_subtree`.
+pub(crate) fn copy_statement<T, C, Cols, R, Sel, F, Q>(
+    table: T,
+    column: C,
+    from: &Ltree,
+    to: &Ltree,
+    remap: R,
+) -> InsertFromSelect<T::FromClause, Cols, Q>
+where
+    T: Table + Copy + FilterDsl<MoveFilter<C>, Output = F>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    R: FnOnce(MovedPath<C>) -> Sel,
+    Sel: Expression,
+    F: SelectDsl<Sel, Output = Q>,
+{
+    let source = table.filter(subtree_filter(column, from));
+    InsertFromSelect {
+        into: table.from_clause(),
+        columns: PhantomData::<Cols>,
+        query: source.select(remap(moved_path(column, from, to))),
+    }
+}
+
+/// Which rows [`delete_subtree`] removes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DeleteMode {
+    /// The node and all of its descendants.
+    Subtree,
+    /// The descendants of the node, keeping the node itself.
+    KeepRoot,
+    /// Only the node, moving each of its descendants up a level so that its
+    /// children are re-parented to its parent.
+    ReparentChildren,
+}
+
+// `column <@ root AND nlevel(column) >= low AND nlevel(column) <= high`
+pub(crate) type DeleteFilter<C> = diesel::dsl::And<
+    diesel::dsl::And<MoveFilter<C>, diesel::dsl::GtEq<nlevel<C>, i32>>,
+    diesel::dsl::LtEq<nlevel<C>, i32>,
+>;
+
+// `column = parent || subpath(column, depth)`
+pub(crate) type ReparentSet<C> = diesel::dsl::Eq<C, Concat<Path, subpath_from<C, i32>>>;
+
+fn delete_filter<C>(column: C, root: &Ltree, low: usize, high: usize) -> DeleteFilter<C>
+where
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+{
+    subtree_filter(column, root)
+        .and(nlevel(column).ge(low as i32))
+        .and(nlevel(column).le(high as i32))
+}
+
+/// Deletes the subtree at `root` as chosen by `mode`, returning the number of
+/// rows deleted.
+///
+/// [`DeleteMode::ReparentChildren`] updates the descendants after deleting
+/// the node, which it does in a transaction (a savepoint, if the caller
+/// already has one open). It is refused with [`SubtreeError::MissingRoot`]
+/// if there is no node at `root`, and with [`SubtreeError::PathTaken`] if a
+/// descendant would be moved onto an existing path, as when deleting `a.b`
+/// would move `a.b.c` to an existing `a.c`.
+pub fn delete_subtree<Conn, T, C, F, W, S, G, V>(
+    conn: &mut Conn,
+    table: T,
+    column: C,
+    root: &Ltree,
+    mode: DeleteMode,
+) -> Result<usize, SubtreeError>
+where
+    Conn: LoadConnection<Backend = Pg>,
+    T: Table + FilterDsl<DeleteFilter<C>, Output = F> + FilterDsl<MoveFilter<C>, Output = G> + Copy,
+    T::FromClause: QueryFragment<Pg>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    F: IntoUpdateTarget<WhereClause = W>,
+    G: IntoUpdateTarget<WhereClause = V>,
+    DeleteStatement<F::Table, W>: ExecuteDsl<Conn>,
+    DeleteStatement<G::Table, V>: ExecuteDsl<Conn>,
+    ReparentSet<C>: AsChangeset<Target = F::Table, Changeset = S>,
+    UpdateStatement<F::Table, W, S>: AsQuery + ExecuteDsl<Conn>,
+{
+    let depth = root.nlevel();
+    match mode {
+        DeleteMode::Subtree => Ok(delete_all_statement(table, column, root).execute(conn)?),
+        DeleteMode::KeepRoot => {
+            Ok(delete_statement(table, column, root, depth + 1, MAX_LEVELS).execute(conn)?)
+        }
+        DeleteMode::ReparentChildren => conn.transaction(|conn| {
+            let deleted = delete_statement(table, column, root, depth, depth).execute(conn)?;
+            if deleted == 0 {
+                return Err(SubtreeError::MissingRoot { root: root.clone() });
+            }
+            let taken = reparent_clash::<_, C>(table, root)
+                .get_result::<Ltree>(conn)
+                .optional()?;
+            if let Some(path) = taken {
+                return Err(SubtreeError::PathTaken { path });
+            }
+            reparent_statement(table, column, root).execute(conn)?;
+            Ok(deleted)
+        }),
+    }
+}
+
+// Shared with the async `delete_subtree`: deletes every row of the subtree at
+// `root`.
+pub(crate) fn delete_all_statement<T, C, G, V>(
+    table: T,
+    column: C,
+    root: &Ltree,
+) -> DeleteStatement<G::Table, V>
+where
+    T: FilterDsl<MoveFilter<C>, Output = G>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    G: IntoUpdateTarget<WhereClause = V>,
+{
+    diesel::delete(table.filter(subtree_filter(column, root)))
+}
+
+// Shared with the async `delete_subtree`: deletes the rows of the subtree at
+// `root` between levels `low` and `high`.
+pub(crate) fn delete_statement<T, C, F, W>(
+    table: T,
+    column: C,
+    root: &Ltree,
+    low: usize,
+    high: usize,
+) -> DeleteStatement<F::Table, W>
+where
+    T: FilterDsl<DeleteFilter<C>, Output = F>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    F: IntoUpdateTarget<WhereClause = W>,
+{
+    diesel::delete(table.filter(delete_filter(column, root, low, high)))
+}
+
+// Moves the descendants of `root` up a level, once `root` is deleted.
+pub(crate) fn reparent_statement<T, C, F, W, S>(
+    table: T,
+    column: C,
+    root: &Ltree,
+) -> UpdateStatement<F::Table, W, S>
+where
+    T: FilterDsl<DeleteFilter<C>, Output = F>,
+    C: Column<SqlType = sql_types::Ltree> + Copy,
+    F: IntoUpdateTarget<WhereClause = W>,
+    ReparentSet<C>: AsChangeset<Target = F::Table, Changeset = S>,
+    UpdateStatement<F::Table, W, S>: AsQuery,
+{
+    let depth = root.nlevel();
+    let parent = root.parent().unwrap_or_else(|| Ltree(String::new()));
+    let set: ReparentSet<C> = column.eq(Concat::new(
+        parent.into_sql::<sql_types::Ltree>(),
+        subpath_from(column, depth as i32),
+    ));
+    diesel::update(table.filter(delete_filter(column, root, depth + 1, MAX_LEVELS))).set(set)
+}
+
+// `SELECT column FROM table WHERE column IN (...) AND NOT column <@ root LIMIT 1`,
+// finding a row outside the subtree at `root` on a path that
+// `reparent_statement` would move one of its descendants to.
+pub(crate) struct ReparentClash<From, C> {
+    from: From,
+    column: PhantomData<C>,
+    root: Ltree,
+    parent: Ltree,
+    depth: i32,
+}
+
+pub(crate) fn reparent_clash<T, C>(table: T, root: &Ltree) -> ReparentClash<T::FromClause, C>
+where
+    T: Table,
+{
+    ReparentClash {
+        from: table.from_clause(),
+        column: PhantomData,
+        root: root.clone(),
+        parent: root.parent().unwrap_or_else(|| Ltree(String::new())),
+        depth: root.nlevel() as i32,
+    }
+}
+
+impl<From, C> QueryFragment<Pg> for ReparentClash<From, C>
+where
+    From: QueryFragment<Pg>,
+    C: Column,
+{
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Pg>) -> QueryResult<()> {
+        out.push_sql("SELECT ");
+        out.push_identifier(C::NAME)?;
+        out.push_sql(" FROM ");
+        self.from.walk_ast(out.reborrow())?;
+        out.push_sql(" WHERE ");
+        out.push_identifier(C::NAME)?;
+        out.push_sql(" IN (SELECT ");
+        out.push_bind_param::<sql_types::Ltree, _>(&self.parent)?;
+        out.push_sql(" || subpath(");
+        out.push_identifier(C::NAME)?;
+        out.push_sql(", ");
+        out.push_bind_param::<diesel::sql_types::Int4, _>(&self.depth)?;
+        out.push_sql(") FROM ");
+        self.from.walk_ast(out.reborrow())?;
+        out.push_sql(" WHERE ");
+        out.push_identifier(C::NAME)?;
+        out.push_sql(" <@ ");
+        out.push_bind_param::<sql_types::Ltree, _>(&self.root)?;
+        out.push_sql(" AND nlevel(");
+        out.push_identifier(C::NAME)?;
+        out.push_sql(") > ");
+        out.push_bind_param::<diesel::sql_types::Int4, _>(&self.depth)?;
+        out.push_sql(") AND NOT ");
+        out.push_identifier(C::NAME)?;
+        out.push_sql(" <@ ");
+        out.push_bind_param::<sql_types::Ltree, _>(&self.root)?;
+        out.push_sql(" LIMIT 1");
+        Ok(())
+    }
+}
+
+impl<From, C> Query for ReparentClash<From, C> {
+    type SqlType = sql_types::Ltree;
+}
+
+impl<From, C> QueryId for ReparentClash<From, C> {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<From, C, Conn> RunQueryDsl<Conn> for ReparentClash<From, C> {}
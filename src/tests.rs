@@ -1,6 +1,10 @@
 extern crate dotenv;
 
+#[cfg(feature = "diesel-async")]
+mod asynchronous;
 mod schema;
+#[cfg(feature = "sqlite")]
+mod sqlite;
 
 use diesel::debug_query;
 use diesel::dsl::array;
@@ -8,15 +12,21 @@ use diesel::pg::Pg;
 use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use diesel::select;
+use diesel::sql_types::{Array, Text};
 use std::env;
 
 use crate::{
-    index, lca, lquery, ltree2text, ltxtquery, nlevel, subltree, subpath, text2ltree,
+    hash_ltree, hash_ltree_extended, index, index_of, lca, lca2, lca3, lca4, lca5, lca6, lca7,
+    lca8, lquery, ltree2text, ltree2text_nullable, ltxtquery, nlevel, subltree, subpath,
+    subpath_from, text2ltree, text2ltree_nullable, DecodeError, Encoding, Label, Lquery,
     LqueryArrayExtensions, LqueryExtensions, Ltree, LtreeArrayExtensions, LtreeExtensions,
-    LtxtqueryExtensions,
+    LtreeParseError, Ltxtquery, LtxtqueryArrayExtensions, LtxtqueryExtensions, TextLtreeExtensions,
+    MAX_LABEL_LENGTH,
 };
 
-use self::schema::my_tree;
+use self::schema::{categories, my_tree};
+use crate::lquery::{self as lq, Expected, Level, Quantifier, Variant};
+use crate::ltxtquery::{self as ltq, Txt};
 
 #[derive(Queryable, Debug)]
 struct MyTree {
@@ -34,6 +44,15 @@ fn get_connection() -> PgConnection {
     connection
 }
 
+fn paths(conn: &mut PgConnection, under: &str) -> QueryResult<Vec<String>> {
+    my_tree::table
+        .select(my_tree::path)
+        .filter(my_tree::path.contained_by(Ltree(under.to_string())))
+        .order(my_tree::path)
+        .load::<Ltree>(conn)
+        .map(|paths| paths.into_iter().map(|p| p.0).collect())
+}
+
 #[test]
 fn base_operations_without_converting_to_text_first() {
     let mut connection = get_connection();
@@ -159,6 +178,180 @@ fn functions() {
     assert_eq!(result, Ok("1.2".into()));
 }
 
+#[test]
+fn function_query_generation() {
+    fn sql<T: diesel::query_builder::QueryFragment<Pg>>(query: T) -> String {
+        debug_query::<Pg, _>(&query).to_string()
+    }
+    let path = || my_tree::path;
+    let from = " FROM \"my_tree\"";
+
+    assert_eq!(
+        sql(my_tree::table.select(subltree(path(), 1, 2))),
+        format!("SELECT subltree(\"my_tree\".\"path\", $1, $2){from} -- binds: [1, 2]")
+    );
+    assert_eq!(
+        sql(my_tree::table.select(subpath(path(), 0, 2))),
+        format!("SELECT subpath(\"my_tree\".\"path\", $1, $2){from} -- binds: [0, 2]")
+    );
+    assert_eq!(
+        sql(my_tree::table.select(subpath_from(path(), -1))),
+        format!("SELECT subpath(\"my_tree\".\"path\", $1){from} -- binds: [-1]")
+    );
+    assert_eq!(
+        sql(my_tree::table.select(nlevel(path()))),
+        format!("SELECT nlevel(\"my_tree\".\"path\"){from} -- binds: []")
+    );
+    assert_eq!(
+        sql(my_tree::table.select(index_of(path(), path()))),
+        format!("SELECT index(\"my_tree\".\"path\", \"my_tree\".\"path\"){from} -- binds: []")
+    );
+    assert_eq!(
+        sql(my_tree::table.select(index(path(), path(), 3))),
+        format!("SELECT index(\"my_tree\".\"path\", \"my_tree\".\"path\", $1){from} -- binds: [3]")
+    );
+    assert_eq!(
+        sql(select(text2ltree("a.b"))),
+        "SELECT text2ltree($1) -- binds: [\"a.b\"]"
+    );
+    assert_eq!(
+        sql(select(text2ltree_nullable(None::<String>))),
+        "SELECT text2ltree($1) -- binds: [None]"
+    );
+    assert_eq!(
+        sql(my_tree::table.select(ltree2text(path()))),
+        format!("SELECT ltree2text(\"my_tree\".\"path\"){from} -- binds: []")
+    );
+    assert_eq!(
+        sql(my_tree::table.select(ltree2text_nullable(path().nullable()))),
+        format!("SELECT ltree2text(\"my_tree\".\"path\"){from} -- binds: []")
+    );
+    assert_eq!(
+        sql(my_tree::table.select(lca(array((path(), path()))))),
+        format!("SELECT lca(ARRAY[\"my_tree\".\"path\", \"my_tree\".\"path\"]){from} -- binds: []")
+    );
+
+    let lcas = [
+        sql(my_tree::table.select(lca2(path(), path()))),
+        sql(my_tree::table.select(lca3(path(), path(), path()))),
+        sql(my_tree::table.select(lca4(path(), path(), path(), path()))),
+        sql(my_tree::table.select(lca5(path(), path(), path(), path(), path()))),
+        sql(my_tree::table.select(lca6(path(), path(), path(), path(), path(), path()))),
+        sql(my_tree::table.select(lca7(path(), path(), path(), path(), path(), path(), path()))),
+        sql(my_tree::table.select(lca8(
+            path(),
+            path(),
+            path(),
+            path(),
+            path(),
+            path(),
+            path(),
+            path(),
+        ))),
+    ];
+    for (arity, query) in (2..).zip(lcas) {
+        let args = vec!["\"my_tree\".\"path\""; arity].join(", ");
+        assert_eq!(query, format!("SELECT lca({args}){from} -- binds: []"));
+    }
+
+    assert_eq!(
+        sql(my_tree::table.select(hash_ltree(path()))),
+        format!("SELECT hash_ltree(\"my_tree\".\"path\"){from} -- binds: []")
+    );
+    assert_eq!(
+        sql(my_tree::table.select(hash_ltree_extended(path(), 42i64))),
+        format!("SELECT hash_ltree_extended(\"my_tree\".\"path\", $1){from} -- binds: [42]")
+    );
+    assert_eq!(
+        sql(select(lquery("a.*"))),
+        "SELECT lquery($1) -- binds: [\"a.*\"]"
+    );
+    assert_eq!(
+        sql(select(ltxtquery("a & b"))),
+        "SELECT ltxtquery($1) -- binds: [\"a & b\"]"
+    );
+}
+
+#[test]
+fn function_overloads() {
+    let mut connection = get_connection();
+
+    let result = select(ltree2text(subpath_from(text2ltree("Top.Child1.Child2"), 1)))
+        .get_result::<String>(&mut connection);
+    assert_eq!(result, Ok("Child1.Child2".into()));
+
+    let result = select(index_of(text2ltree("0.1.2.3.5.4.5.6"), text2ltree("5.6")))
+        .get_result::<i32>(&mut connection);
+    assert_eq!(result, Ok(6));
+
+    let result = select((
+        text2ltree_nullable(Some("a.b")),
+        text2ltree_nullable(None::<String>),
+        ltree2text_nullable(None::<Ltree>),
+    ))
+    .get_result::<(Option<Ltree>, Option<Ltree>, Option<String>)>(&mut connection);
+    assert_eq!(result, Ok((Some(Ltree("a.b".to_string())), None, None)));
+
+    let result = select((
+        ltree2text(lca2(text2ltree("1.2.3"), text2ltree("1.2.4"))),
+        ltree2text(lca3(
+            text2ltree("1.2.3"),
+            text2ltree("1.2.4"),
+            text2ltree("1.5"),
+        )),
+        ltree2text(lca8(
+            text2ltree("1.2.3"),
+            text2ltree("1.2.4"),
+            text2ltree("1.2.5"),
+            text2ltree("1.2.6"),
+            text2ltree("1.2.7"),
+            text2ltree("1.2.8"),
+            text2ltree("1.2.9"),
+            text2ltree("1.2.3.4"),
+        )),
+    ))
+    .get_result::<(String, String, String)>(&mut connection);
+    assert_eq!(result, Ok(("1.2".into(), "1".into(), "1.2".into())));
+
+    let result = select((
+        ltree2text(lca4(
+            text2ltree("1.2.3"),
+            text2ltree("1.2.4"),
+            text2ltree("1.2.5"),
+            text2ltree("1.3"),
+        )),
+        ltree2text(lca5(
+            text2ltree("1.2.3"),
+            text2ltree("1.2.4"),
+            text2ltree("1.2.5"),
+            text2ltree("1.2.6"),
+            text2ltree("1.2.7"),
+        )),
+        ltree2text(lca6(
+            text2ltree("a.b.c"),
+            text2ltree("a.b.d"),
+            text2ltree("a.b.e"),
+            text2ltree("a.b.f"),
+            text2ltree("a.b.g"),
+            text2ltree("a.b.c.d"),
+        )),
+        ltree2text(lca7(
+            text2ltree("x.y.a"),
+            text2ltree("x.y.b"),
+            text2ltree("x.y.c"),
+            text2ltree("x.y.d"),
+            text2ltree("x.y.e"),
+            text2ltree("x.y.f"),
+            text2ltree("x.z"),
+        )),
+    ))
+    .get_result::<(String, String, String, String)>(&mut connection);
+    assert_eq!(
+        result,
+        Ok(("1".into(), "1.2".into(), "a.b".into(), "x".into()))
+    );
+}
+
 #[test]
 fn operators() {
     let mut connection = get_connection();
@@ -250,20 +443,1700 @@ fn operators() {
     assert_eq!(result, Ok((true, true)));
 
     let result = select((
-        ltree2text(array((text2ltree("a.b.c"), text2ltree("a"))).first_contains(text2ltree("a.b"))),
-        ltree2text(
+        ltree2text_nullable(
+            array((text2ltree("a.b.c"), text2ltree("a"))).first_contains(text2ltree("a.b")),
+        ),
+        ltree2text_nullable(
             array((text2ltree("a"), text2ltree("a.b.c"))).first_contained_by(text2ltree("a.b")),
         ),
     ))
-    .get_result::<(String, String)>(&mut connection);
-    assert_eq!(result, Ok(("a".into(), "a.b.c".into())));
+    .get_result::<(Option<String>, Option<String>)>(&mut connection);
+    assert_eq!(result, Ok((Some("a".into()), Some("a.b.c".into()))));
 
     let result = select((
-        ltree2text(array((text2ltree("a.b.c"), text2ltree("a"))).first_matches(lquery("a%"))),
-        ltree2text(
+        ltree2text_nullable(
+            array((text2ltree("a.b.c"), text2ltree("a"))).first_matches(lquery("a%")),
+        ),
+        ltree2text_nullable(
             array((text2ltree("a"), text2ltree("a.b.c"))).first_tmatches(ltxtquery("a & b")),
         ),
     ))
+    .get_result::<(Option<String>, Option<String>)>(&mut connection);
+    assert_eq!(result, Ok((Some("a".into()), Some("a.b.c".into()))));
+
+    let result = select((
+        array((text2ltree("a"), text2ltree("b"))).first_contains(text2ltree("c.d")),
+        array((text2ltree("a"), text2ltree("b"))).first_matches(lquery("c.*")),
+    ))
+    .get_result::<(Option<Ltree>, Option<Ltree>)>(&mut connection);
+    assert_eq!(result, Ok((None, None)));
+
+    let result = select((
+        ltree2text(text2ltree("a.b").concat_text("c.d")),
+        ltree2text("a.b".into_sql::<Text>().concat_ltree(text2ltree("c.d"))),
+    ))
     .get_result::<(String, String)>(&mut connection);
-    assert_eq!(result, Ok(("a".into(), "a.b.c".into())));
+    assert_eq!(result, Ok(("a.b.c.d".into(), "a.b.c.d".into())));
+
+    let result = select((
+        text2ltree("a.b").lt(text2ltree("a.b.c")),
+        text2ltree("a.b").le(text2ltree("a.b")),
+        text2ltree("a_b").gt(text2ltree("a.b")),
+        text2ltree("B").ge(text2ltree("a")),
+        array((text2ltree("a"), text2ltree("a.c"))).gt(array((text2ltree("a"), text2ltree("a.b")))),
+    ))
+    .get_result::<(bool, bool, bool, bool, bool)>(&mut connection);
+    assert_eq!(result, Ok((true, true, true, false, true)));
+}
+
+#[test]
+fn array_operations() {
+    use crate::sql_types;
+
+    let mut connection = get_connection();
+    let path = || text2ltree("a.b");
+    let paths = || array((text2ltree("x"), text2ltree("a.b")));
+    let lqueries = || array((lquery("x"), lquery("a.*")));
+    let ltxtqueries = || vec![Ltxtquery("x".to_string()), Ltxtquery("a & b".to_string())];
+
+    // `~` and `?`, from each side.
+    let result = select((
+        path().matches(lquery("a.*")),
+        path().matches_any(lqueries()),
+        paths().any_matches(lquery("a.*")),
+        paths().any_matches_any(lqueries()),
+        lquery("a.*").matches(path()),
+        lquery("a.*").matches_any(paths()),
+        lqueries().any_matches(path()),
+        lqueries().any_matches_any(paths()),
+    ))
+    .get_result::<(bool, bool, bool, bool, bool, bool, bool, bool)>(&mut connection);
+    assert_eq!(result, Ok((true, true, true, true, true, true, true, true)));
+
+    // `@`, from each side.
+    let result = select((
+        path().tmatches(ltxtquery("a & b")),
+        path().tmatches_any(ltxtqueries()),
+        paths().any_tmatches(ltxtquery("a & b")),
+        paths().any_tmatches_any(ltxtqueries()),
+        ltxtquery("a & b").tmatches(path()),
+        ltxtquery("a & b").tmatches_any(paths()),
+        ltxtqueries()
+            .into_sql::<Array<sql_types::Ltxtquery>>()
+            .any_tmatches(path()),
+        array((ltxtquery("x"), ltxtquery("a & b"))).any_tmatches_any(paths()),
+    ))
+    .get_result::<(bool, bool, bool, bool, bool, bool, bool, bool)>(&mut connection);
+    assert_eq!(result, Ok((true, true, true, true, true, true, true, true)));
+
+    let result = select((
+        text2ltree("c").tmatches_any(ltxtqueries()),
+        array((text2ltree("c"),)).any_tmatches_any(ltxtqueries()),
+        path().tmatches_any(Vec::<Ltxtquery>::new()),
+        path().nullable().tmatches_any(None::<Vec<Ltxtquery>>),
+    ))
+    .get_result::<(bool, bool, bool, Option<bool>)>(&mut connection);
+    assert_eq!(result, Ok((false, false, false, None)));
+
+    let query =
+        debug_query::<Pg, _>(&my_tree::table.select(my_tree::path.tmatches_any(ltxtqueries())))
+            .to_string();
+    assert_eq!(
+        query,
+        "SELECT \"my_tree\".\"path\" @ ANY($1) FROM \"my_tree\" \
+         -- binds: [[Ltxtquery(\"x\"), Ltxtquery(\"a & b\")]]"
+    );
+}
+
+#[test]
+fn first_match_helpers() {
+    let mut connection = get_connection();
+
+    let access_roots = vec![
+        Ltree("root.bacteria".to_string()),
+        Ltree("root.eukaryota.plantae".to_string()),
+        Ltree("root.eukaryota".to_string()),
+    ];
+    let result = my_tree::table
+        .select((my_tree::id, my_tree::path.first_ancestor_in(&access_roots)))
+        .filter(my_tree::id.eq_any([1, 3, 5, 11, 14]))
+        .order(my_tree::id)
+        .load::<(i32, Option<Ltree>)>(&mut connection);
+    assert_eq!(
+        result,
+        Ok(vec![
+            (1, None),
+            (3, Some(Ltree("root.bacteria".to_string()))),
+            (5, None),
+            (11, Some(Ltree("root.eukaryota.plantae".to_string()))),
+            (14, Some(Ltree("root.eukaryota".to_string()))),
+        ])
+    );
+
+    let paths = array((text2ltree("a.b"), text2ltree("a.c"), text2ltree("b.c")));
+    let result = select((
+        text2ltree("a").first_descendant_in(paths),
+        lquery("*.c").first_match_in(paths),
+        ltxtquery("b & c").first_tmatch_in(paths),
+        lquery("x").first_match_in(array((text2ltree("a"),))),
+    ))
+    .get_result::<(Option<Ltree>, Option<Ltree>, Option<Ltree>, Option<Ltree>)>(&mut connection);
+    assert_eq!(
+        result,
+        Ok((
+            Some(Ltree("a.b".to_string())),
+            Some(Ltree("a.c".to_string())),
+            Some(Ltree("b.c".to_string())),
+            None
+        ))
+    );
+}
+
+#[test]
+fn move_subtree() {
+    use crate::subtree::{move_statement, move_subtree, SubtreeError};
+
+    let mut connection = get_connection();
+    let statement = move_statement(
+        my_tree::table,
+        my_tree::path,
+        &Ltree("a.b".to_string()),
+        &Ltree("c".to_string()),
+    )
+    .unwrap();
+    assert_eq!(
+        debug_query::<Pg, _>(&statement).to_string(),
+        "UPDATE \"my_tree\" SET \"path\" = $1 || subpath(\"my_tree\".\"path\" || $2, $3, $4) \
+         WHERE \"my_tree\".\"path\" <@ $5 \
+         -- binds: [Ltree(\"c\"), Ltree(\"_\"), 2, -1, Ltree(\"a.b\")]"
+    );
+
+    let result = move_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.plantae".to_string()),
+        &Ltree("root.archaea.flora".to_string()),
+    );
+    assert_eq!(result, Ok(3));
+    assert_eq!(
+        paths(&mut connection, "root.archaea.flora"),
+        Ok(vec![
+            "root.archaea.flora".to_string(),
+            "root.archaea.flora.chlorophyta".to_string(),
+            "root.archaea.flora.nematophyta".to_string(),
+        ])
+    );
+    assert_eq!(paths(&mut connection, "root.eukaryota.plantae"), Ok(vec![]));
+
+    let result = move_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.bacteria".to_string()),
+        &Ltree("bacteria".to_string()),
+    );
+    assert_eq!(result, Ok(3));
+    assert_eq!(
+        paths(&mut connection, "bacteria"),
+        Ok(vec![
+            "bacteria".to_string(),
+            "bacteria.aquificae".to_string(),
+            "bacteria.thermotogae".to_string(),
+        ])
+    );
+
+    let result = move_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota".to_string()),
+        &Ltree("root.eukaryota.animalia.eukaryota".to_string()),
+    );
+    assert_eq!(
+        result,
+        Err(SubtreeError::IntoOwnSubtree {
+            from: Ltree("root.eukaryota".to_string()),
+            to: Ltree("root.eukaryota.animalia.eukaryota".to_string()),
+        })
+    );
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota").map(|paths| paths.len()),
+        Ok(4)
+    );
+}
+
+#[test]
+fn copy_subtree() {
+    use crate::subtree::copy_subtree;
+
+    let mut connection = get_connection();
+
+    let result = copy_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.plantae".to_string()),
+        &Ltree("root.archaea.flora".to_string()),
+        (my_tree::path,),
+        |path| (path,),
+    );
+    assert_eq!(result, Ok(3));
+    assert_eq!(
+        paths(&mut connection, "root.archaea.flora"),
+        Ok(vec![
+            "root.archaea.flora".to_string(),
+            "root.archaea.flora.chlorophyta".to_string(),
+            "root.archaea.flora.nematophyta".to_string(),
+        ])
+    );
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota.plantae").map(|paths| paths.len()),
+        Ok(3)
+    );
+}
+
+#[test]
+fn delete_subtree() {
+    use crate::subtree::{delete_all_statement, delete_subtree, DeleteMode, SubtreeError};
+
+    let mut connection = get_connection();
+
+    let root = Ltree("root.bacteria".to_string());
+    assert_eq!(
+        debug_query::<Pg, _>(&delete_all_statement(my_tree::table, my_tree::path, &root))
+            .to_string(),
+        "DELETE FROM \"my_tree\" WHERE \"my_tree\".\"path\" <@ $1 \
+         -- binds: [Ltree(\"root.bacteria\")]"
+    );
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &root,
+        DeleteMode::Subtree,
+    );
+    assert_eq!(result, Ok(3));
+    assert_eq!(paths(&mut connection, "root.bacteria"), Ok(vec![]));
+
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.plantae".to_string()),
+        DeleteMode::KeepRoot,
+    );
+    assert_eq!(result, Ok(2));
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota.plantae"),
+        Ok(vec!["root.eukaryota.plantae".to_string()])
+    );
+
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.animalia".to_string()),
+        DeleteMode::ReparentChildren,
+    );
+    assert_eq!(result, Ok(1));
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota"),
+        Ok(vec![
+            "root.eukaryota".to_string(),
+            "root.eukaryota.chancelloriidae".to_string(),
+            "root.eukaryota.cloudinidae".to_string(),
+            "root.eukaryota.plantae".to_string(),
+        ])
+    );
+
+    diesel::insert_into(my_tree::table)
+        .values(&vec![
+            my_tree::path.eq(Ltree("root.eukaryota.fungi.agaricales".to_string())),
+            my_tree::path.eq(Ltree("root.eukaryota.plantae.cloudinidae".to_string())),
+        ])
+        .execute(&mut connection)
+        .unwrap();
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.fungi".to_string()),
+        DeleteMode::ReparentChildren,
+    );
+    assert_eq!(
+        result,
+        Err(SubtreeError::MissingRoot {
+            root: Ltree("root.eukaryota.fungi".to_string())
+        })
+    );
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota.fungi"),
+        Ok(vec!["root.eukaryota.fungi.agaricales".to_string()])
+    );
+
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.plantae".to_string()),
+        DeleteMode::ReparentChildren,
+    );
+    assert_eq!(
+        result,
+        Err(SubtreeError::PathTaken {
+            path: Ltree("root.eukaryota.cloudinidae".to_string())
+        })
+    );
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota.plantae"),
+        Ok(vec![
+            "root.eukaryota.plantae".to_string(),
+            "root.eukaryota.plantae.cloudinidae".to_string(),
+        ])
+    );
+}
+
+#[test]
+fn adjacency_list() {
+    use crate::adjacency::{self, AdjacencyError, ColumnLabels, CustomLabels, IdLabels};
+
+    let mut connection = get_connection();
+    let paths = |pairs: Vec<(i32, Ltree)>| {
+        pairs
+            .into_iter()
+            .map(|(id, path)| (id, path.0))
+            .collect::<Vec<_>>()
+    };
+    let expected = |labels: [&str; 6]| {
+        let [a, b, c, d, e, f] = labels;
+        vec![
+            (1, a.to_string()),
+            (2, format!("{}.{}", a, b)),
+            (4, format!("{}.{}.{}", a, b, d)),
+            (3, format!("{}.{}", a, c)),
+            (5, e.to_string()),
+            (6, format!("{}.{}", e, f)),
+        ]
+    };
+
+    let result = adjacency::dry_run(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        IdLabels,
+    );
+    assert_eq!(
+        result.map(paths),
+        Ok(expected(["1", "2", "3", "4", "5", "6"]))
+    );
+    let result = adjacency::dry_run(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        ColumnLabels(categories::slug),
+    );
+    assert_eq!(
+        result.map(paths),
+        Ok(expected([
+            "animals", "birds", "fish", "owls", "plants", "trees"
+        ]))
+    );
+    let result = adjacency::dry_run(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        CustomLabels::new(|id: &i32| format!("c{}", id)),
+    );
+    assert_eq!(
+        result.map(paths),
+        Ok(expected(["c1", "c2", "c3", "c4", "c5", "c6"]))
+    );
+
+    let filled = |conn: &mut PgConnection| {
+        categories::table
+            .filter(categories::path.is_not_null())
+            .count()
+            .get_result::<i64>(conn)
+    };
+    // A dry run changes nothing.
+    assert_eq!(filled(&mut connection), Ok(0));
+
+    diesel::insert_into(categories::table)
+        .values(&vec![
+            (
+                categories::id.eq(7),
+                categories::parent_id.eq(Some(99)),
+                categories::slug.eq(Some("lost")),
+            ),
+            (
+                categories::id.eq(8),
+                categories::parent_id.eq(Some(7)),
+                categories::slug.eq(Some("found")),
+            ),
+        ])
+        .execute(&mut connection)
+        .unwrap();
+    let result = adjacency::dry_run(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        IdLabels,
+    );
+    assert_eq!(result, Err(AdjacencyError::Orphan { id: 7, parent: 99 }));
+
+    diesel::update(categories::table.filter(categories::id.eq(7)))
+        .set(categories::parent_id.eq(Some(8)))
+        .execute(&mut connection)
+        .unwrap();
+    let result = adjacency::fill(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        IdLabels,
+        categories::path,
+    );
+    assert_eq!(result, Err(AdjacencyError::Cycle { ids: vec![8, 7] }));
+    assert_eq!(filled(&mut connection), Ok(0));
+    diesel::delete(categories::table.filter(categories::id.ge(7)))
+        .execute(&mut connection)
+        .unwrap();
+
+    diesel::update(categories::table.filter(categories::id.eq(3)))
+        .set(categories::slug.eq(None::<String>))
+        .execute(&mut connection)
+        .unwrap();
+    let result = adjacency::fill(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        ColumnLabels(categories::slug),
+        categories::path,
+    );
+    assert_eq!(result, Err(AdjacencyError::NullLabel { id: 3 }));
+    assert_eq!(filled(&mut connection), Ok(0));
+
+    let result: Result<_, AdjacencyError<i32>> = adjacency::fill(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        IdLabels,
+        categories::path,
+    );
+    assert_eq!(result, Ok(6));
+    let filled = categories::table
+        .select((categories::id, categories::path))
+        .order(categories::path)
+        .load::<(i32, Option<Ltree>)>(&mut connection)
+        .map(|rows| {
+            rows.into_iter()
+                .map(|(id, path)| (id, path.unwrap().0))
+                .collect::<Vec<_>>()
+        });
+    assert_eq!(filled, Ok(expected(["1", "2", "3", "4", "5", "6"])));
+}
+
+#[test]
+fn index_management() {
+    use crate::schema::{create_extension, create_index, unindexed_columns, IndexMethod};
+
+    diesel::table! {
+        use diesel::sql_types::*;
+        use crate::sql_types::Ltree;
+
+        tagged (id) {
+            id -> Int4,
+            paths -> Nullable<Array<Ltree>>,
+        }
+    }
+
+    let mut connection = get_connection();
+    let unindexed = |conn: &mut PgConnection, method| {
+        unindexed_columns(conn, method).map(|columns| {
+            columns
+                .into_iter()
+                .filter(|c| c.schema == "public")
+                .map(|c| (c.table, c.column, c.is_array))
+                .collect::<Vec<_>>()
+        })
+    };
+    let column =
+        |table: &str, column: &str, is_array| (table.to_string(), column.to_string(), is_array);
+
+    assert_eq!(
+        debug_query::<Pg, _>(&create_extension()).to_string(),
+        "CREATE EXTENSION IF NOT EXISTS ltree -- binds: []"
+    );
+    assert_eq!(create_extension().execute(&mut connection), Ok(0));
+
+    let gist = create_index(
+        "my_tree_path_gist",
+        my_tree::table,
+        my_tree::path,
+        IndexMethod::Gist,
+    );
+    assert_eq!(
+        debug_query::<Pg, _>(&gist.clone().siglen(100)).to_string(),
+        "CREATE INDEX IF NOT EXISTS \"my_tree_path_gist\" ON \"my_tree\" \
+         USING gist (\"path\" gist_ltree_ops(siglen = 100)) -- binds: []"
+    );
+    assert_eq!(
+        debug_query::<Pg, _>(&gist).to_string(),
+        "CREATE INDEX IF NOT EXISTS \"my_tree_path_gist\" ON \"my_tree\" \
+         USING gist (\"path\" gist_ltree_ops) -- binds: []"
+    );
+    let hash = create_index(
+        "my_tree_path_hash",
+        my_tree::table,
+        my_tree::path,
+        IndexMethod::Hash,
+    );
+    assert_eq!(
+        debug_query::<Pg, _>(&hash).to_string(),
+        "CREATE INDEX IF NOT EXISTS \"my_tree_path_hash\" ON \"my_tree\" \
+         USING hash (\"path\") -- binds: []"
+    );
+    assert!(hash.siglen(8).execute(&mut connection).is_err());
+    for siglen in [0, 5, 3000] {
+        let result = gist.clone().siglen(siglen).execute(&mut connection);
+        assert!(
+            matches!(result, Err(diesel::result::Error::QueryBuilderError(_))),
+            "siglen = {}: {:?}",
+            siglen,
+            result
+        );
+    }
+
+    diesel::sql_query("CREATE TABLE tagged (id SERIAL PRIMARY KEY, paths ltree[])")
+        .execute(&mut connection)
+        .unwrap();
+    assert_eq!(
+        unindexed(&mut connection, IndexMethod::Gist),
+        Ok(vec![
+            column("categories", "path", false),
+            column("my_tree", "path", false),
+            column("tagged", "paths", true),
+        ])
+    );
+
+    assert_eq!(gist.siglen(100).execute(&mut connection), Ok(0));
+    let result = create_index(
+        "tagged_paths_gist",
+        tagged::table,
+        tagged::paths,
+        IndexMethod::Gist,
+    )
+    .siglen(60)
+    .execute(&mut connection);
+    assert_eq!(result, Ok(0));
+    let result = create_index(
+        "categories_path_btree",
+        categories::table,
+        categories::path,
+        IndexMethod::Btree,
+    )
+    .execute(&mut connection);
+    assert_eq!(result, Ok(0));
+
+    assert_eq!(
+        unindexed(&mut connection, IndexMethod::Gist),
+        Ok(vec![column("categories", "path", false)])
+    );
+    assert_eq!(
+        unindexed(&mut connection, IndexMethod::Btree),
+        Ok(vec![
+            column("my_tree", "path", false),
+            column("tagged", "paths", true),
+        ])
+    );
+}
+
+#[test]
+fn tree_from_rows() {
+    use crate::tree::{Forest, Tree};
+
+    let mut connection = get_connection();
+    let rows = my_tree::table
+        .select((my_tree::path, my_tree::id))
+        .order(my_tree::id.desc())
+        .load::<(Ltree, i32)>(&mut connection)
+        .unwrap();
+    let mut forest = rows.into_iter().collect::<Forest<i32>>();
+    fn paths<'a>(trees: impl IntoIterator<Item = &'a Tree<i32>>) -> Vec<&'a str> {
+        trees.into_iter().map(|t| t.path().0.as_str()).collect()
+    }
+
+    assert_eq!(forest.len(), 15);
+    assert_eq!(forest.roots().len(), 1);
+    assert_eq!(
+        paths(forest.roots()[0].children()),
+        vec![
+            "root.archaea",
+            "root.archeae.thermoprotei",
+            "root.bacteria",
+            "root.eukaryota"
+        ]
+    );
+    // root.archaea.thermoprotei was never inserted, so its children hang off
+    // root.archaea.
+    let archaea = forest.get(&Ltree("root.archaea".to_string())).unwrap();
+    assert_eq!(*archaea.value(), 5);
+    assert_eq!(
+        paths(archaea.children()),
+        vec![
+            "root.archaea.thermoprotei.pyrodictiaceae",
+            "root.archaea.thermoprotei.thermoproteaceae"
+        ]
+    );
+    assert!(forest
+        .get(&Ltree("root.archaea.thermoprotei".to_string()))
+        .is_none());
+
+    let eukaryota = forest.get(&Ltree("root.eukaryota".to_string())).unwrap();
+    assert_eq!(
+        paths(eukaryota.depth_first()),
+        vec![
+            "root.eukaryota",
+            "root.eukaryota.animalia",
+            "root.eukaryota.animalia.chancelloriidae",
+            "root.eukaryota.animalia.cloudinidae",
+            "root.eukaryota.plantae",
+            "root.eukaryota.plantae.chlorophyta",
+            "root.eukaryota.plantae.nematophyta",
+        ]
+    );
+    assert_eq!(
+        paths(eukaryota.breadth_first()),
+        vec![
+            "root.eukaryota",
+            "root.eukaryota.animalia",
+            "root.eukaryota.plantae",
+            "root.eukaryota.animalia.chancelloriidae",
+            "root.eukaryota.animalia.cloudinidae",
+            "root.eukaryota.plantae.chlorophyta",
+            "root.eukaryota.plantae.nematophyta",
+        ]
+    );
+
+    let bacteria = forest.remove(&Ltree("root.bacteria".to_string())).unwrap();
+    assert_eq!(
+        paths(bacteria.depth_first()),
+        vec![
+            "root.bacteria",
+            "root.bacteria.aquificae",
+            "root.bacteria.thermotogae"
+        ]
+    );
+    assert_eq!(forest.len(), 12);
+    assert!(forest
+        .get(&Ltree("root.bacteria.aquificae".to_string()))
+        .is_none());
+}
+
+#[test]
+fn operator_query_generation() {
+    let query = debug_query::<Pg, _>(&my_tree::table.select((
+        my_tree::path.concat_text("c"),
+        "c".into_sql::<Text>().concat_ltree(my_tree::path),
+        array((my_tree::path,)).first_contains(my_tree::path),
+    )))
+    .to_string();
+    assert_eq!(
+        query,
+        "SELECT \"my_tree\".\"path\" || $1, $2 || \"my_tree\".\"path\", \
+         ARRAY[\"my_tree\".\"path\"] ?@> \"my_tree\".\"path\" \
+         FROM \"my_tree\" -- binds: [\"c\", \"c\"]"
+    );
+}
+
+#[test]
+fn parse_ltree() {
+    assert_eq!(Ltree::parse(""), Ok(Ltree("".to_string())));
+    assert_eq!(
+        "Top.Child_1.child2".parse::<Ltree>(),
+        Ok(Ltree("Top.Child_1.child2".to_string()))
+    );
+    if cfg!(feature = "hyphen-labels") {
+        assert_eq!(
+            "Top.Child_1.child-2".parse::<Ltree>(),
+            Ok(Ltree("Top.Child_1.child-2".to_string()))
+        );
+    } else {
+        assert_eq!(
+            "Top.Child_1.child-2".parse::<Ltree>(),
+            Err(LtreeParseError::InvalidCharacter {
+                offset: 17,
+                character: '-'
+            })
+        );
+    }
+    assert_eq!(Ltree::try_from("a.b.c"), Ok(Ltree("a.b.c".to_string())));
+
+    assert_eq!(
+        Ltree::parse("a..b"),
+        Err(LtreeParseError::EmptyLabel { offset: 2 })
+    );
+    assert_eq!(
+        Ltree::parse("a.b."),
+        Err(LtreeParseError::EmptyLabel { offset: 4 })
+    );
+    assert_eq!(
+        Ltree::parse("a.b c"),
+        Err(LtreeParseError::InvalidCharacter {
+            offset: 3,
+            character: ' '
+        })
+    );
+    assert_eq!(Ltree::parse("a.é").map_err(|e| e.offset()), Err(2));
+
+    let long = "x".repeat(1001);
+    assert_eq!(
+        Ltree::parse(&format!("a.{}", long)),
+        Err(LtreeParseError::LabelTooLong {
+            offset: 2,
+            length: 1001
+        })
+    );
+    assert!(Ltree::parse(&"x".repeat(1000)).is_ok());
+
+    let deep = vec!["a"; 65535].join(".");
+    assert!(Ltree::parse(&deep).is_ok());
+    assert_eq!(
+        Ltree::parse(&format!("{}.a", deep)),
+        Err(LtreeParseError::TooManyLevels {
+            offset: deep.len() + 1
+        })
+    );
+}
+
+#[test]
+fn parse_ltree_agrees_with_postgres() {
+    let mut connection = get_connection();
+    let version = diesel::dsl::sql::<diesel::sql_types::Integer>(
+        "current_setting('server_version_num')::int",
+    );
+    let version = select(version).get_result::<i32>(&mut connection).unwrap();
+    // Only PostgreSQL 16 accepts `-`, which `parse` follows with the
+    // `hyphen-labels` feature.
+    let hyphens = version >= 160000;
+    assert_eq!(Ltree::parse("a-b").is_ok(), cfg!(feature = "hyphen-labels"));
+
+    let inputs = [
+        "", "a", "a.b_c.D1", "a..b", ".a", "a.", "a b", "a.b!c", "a-b", "a.é",
+    ];
+    for input in inputs {
+        let theirs = connection.transaction::<String, diesel::result::Error, _>(|conn| {
+            select(ltree2text(text2ltree(input))).get_result(conn)
+        });
+        if input.contains('-') {
+            assert_eq!(theirs.is_ok(), hyphens, "server on {:?}", input);
+            if hyphens != cfg!(feature = "hyphen-labels") {
+                continue;
+            }
+        }
+        let ours = Ltree::parse(input);
+        assert_eq!(ours.is_ok(), theirs.is_ok(), "disagreement on {:?}", input);
+        if let (Ok(ours), Ok(theirs)) = (ours, theirs) {
+            assert_eq!(ours.0, theirs);
+        }
+    }
+}
+
+#[test]
+fn path_algebra() {
+    let path = Ltree("Top.Child1.Child2".to_string());
+    let ltree = |s: &str| Some(Ltree(s.to_string()));
+
+    assert_eq!(path.nlevel(), 3);
+    assert_eq!(Ltree("".to_string()).nlevel(), 0);
+    assert_eq!(path.subltree(1, 2), ltree("Child1"));
+    assert_eq!(path.subpath(0, 2), ltree("Top.Child1"));
+    assert_eq!(path.subpath(-2, 1), ltree("Child1"));
+    assert_eq!(path.subpath(0, -1), ltree("Top.Child1"));
+    assert_eq!(path.subpath(3, 1), None);
+    assert_eq!(path.parent(), ltree("Top.Child1"));
+    assert_eq!(Ltree("Top".to_string()).parent(), None);
+    assert_eq!(
+        Ltree("0.1.2.3.5.4.5.6.8.5.6.8".to_string()).index_of(&Ltree("5.6".to_string()), 0),
+        Some(6)
+    );
+    assert_eq!(
+        Ltree("a.b".to_string()).concat(&Ltree("c.d".to_string())),
+        ltree("a.b.c.d")
+    );
+    assert_eq!(
+        Ltree::lca(&[Ltree("1.2.2.3".to_string()), Ltree("1.2.3".to_string())]),
+        ltree("1.2")
+    );
+    assert_eq!(Ltree::lca(&[]), None);
+    assert!(Ltree("Top".to_string()).is_ancestor_of(&path));
+    assert!(path.is_descendant_of(&Ltree("Top.Child1".to_string())));
+    assert!(!path.is_descendant_of(&Ltree("Top.Child".to_string())));
+
+    let roots = [
+        Ltree("Top.Child2".to_string()),
+        Ltree("Top.Child1".to_string()),
+        Ltree("Top".to_string()),
+    ];
+    assert_eq!(path.first_ancestor_in(&roots), Some(&roots[1]));
+    assert_eq!(roots[2].first_descendant_in(&roots), Some(&roots[0]));
+    assert_eq!(Ltree("Other".to_string()).first_ancestor_in(&roots), None);
+}
+
+#[test]
+fn path_algebra_agrees_with_postgres() {
+    let mut connection = get_connection();
+    let paths = ["", "a", "a.b.c.d.e", "x.a.b.x.a.b"];
+
+    for path in paths {
+        let ours = Ltree(path.to_string());
+        for offset in -12..12 {
+            for len in -7..7 {
+                let theirs = connection.transaction::<String, diesel::result::Error, _>(|conn| {
+                    select(ltree2text(subpath(text2ltree(path), offset, len))).get_result(conn)
+                });
+                assert_eq!(
+                    ours.subpath(offset, len).map(|t| t.0),
+                    theirs.ok(),
+                    "subpath({:?}, {}, {})",
+                    path,
+                    offset,
+                    len
+                );
+
+                let theirs = connection.transaction::<String, diesel::result::Error, _>(|conn| {
+                    select(ltree2text(subltree(text2ltree(path), offset, len))).get_result(conn)
+                });
+                assert_eq!(
+                    ours.subltree(offset, len).map(|t| t.0),
+                    theirs.ok(),
+                    "subltree({:?}, {}, {})",
+                    path,
+                    offset,
+                    len
+                );
+            }
+
+            for needle in ["", "a", "a.b", "b.x.a"] {
+                let theirs = select(index(text2ltree(path), text2ltree(needle), offset))
+                    .get_result::<i32>(&mut connection)
+                    .unwrap();
+                assert_eq!(
+                    ours.index_of(&Ltree(needle.to_string()), offset)
+                        .map_or(-1, |i| i as i32),
+                    theirs,
+                    "index({:?}, {:?}, {})",
+                    path,
+                    needle,
+                    offset
+                );
+            }
+        }
+
+        for other in paths {
+            let other = Ltree(other.to_string());
+            let theirs = select((
+                text2ltree(path).contains(text2ltree(&other.0)),
+                text2ltree(path).contained_by(text2ltree(&other.0)),
+                ltree2text(text2ltree(path).concat(text2ltree(&other.0))),
+            ))
+            .get_result::<(bool, bool, String)>(&mut connection)
+            .unwrap();
+            assert_eq!(
+                (
+                    ours.is_ancestor_of(&other),
+                    ours.is_descendant_of(&other),
+                    ours.concat(&other).unwrap().0
+                ),
+                theirs
+            );
+
+            let theirs = select(lca(array((text2ltree(path), text2ltree(&other.0)))).nullable())
+                .get_result::<Option<Ltree>>(&mut connection)
+                .unwrap();
+            assert_eq!(Ltree::lca(&[ours.clone(), other]), theirs);
+        }
+    }
+}
+
+#[test]
+fn labels() {
+    let path = Ltree("root.eukaryota.plantae".to_string());
+    let empty = Ltree("".to_string());
+
+    assert_eq!(
+        path.labels().collect::<Vec<_>>(),
+        ["root", "eukaryota", "plantae"]
+    );
+    assert_eq!(
+        path.labels()
+            .rev()
+            .map(|l| l.to_string())
+            .collect::<Vec<_>>(),
+        ["plantae", "eukaryota", "root"]
+    );
+    assert_eq!(empty.labels().count(), 0);
+    assert_eq!(path.first(), Some(Label::new("root").unwrap()));
+    assert_eq!(path.last().map(|l| l.as_str()), Some("plantae"));
+    assert_eq!(empty.first(), None);
+
+    assert_eq!(
+        path.iter_prefixes().collect::<Vec<_>>(),
+        ["root", "root.eukaryota", "root.eukaryota.plantae"]
+    );
+    assert_eq!(
+        path.iter_prefixes().rev().collect::<Vec<_>>(),
+        ["root.eukaryota.plantae", "root.eukaryota", "root"]
+    );
+    assert_eq!(empty.iter_prefixes().next(), None);
+
+    assert_eq!(
+        path.ancestors().collect::<Vec<_>>(),
+        [
+            Ltree("root.eukaryota".to_string()),
+            Ltree("root".to_string())
+        ]
+    );
+    assert_eq!(
+        path.ancestors().next_back(),
+        Some(Ltree("root".to_string()))
+    );
+    assert_eq!(Ltree("root".to_string()).ancestors().next(), None);
+
+    let mut built = path.labels().skip(1).collect::<Ltree>();
+    assert_eq!(built, Ltree("eukaryota.plantae".to_string()));
+    built.extend([Label::new("chlorophyta").unwrap()]);
+    assert_eq!(built.to_string(), "eukaryota.plantae.chlorophyta");
+
+    assert_eq!(
+        Label::new("a.b"),
+        Err(LtreeParseError::InvalidCharacter {
+            offset: 1,
+            character: '.'
+        })
+    );
+}
+
+#[test]
+fn label_encodings() {
+    let names = ["Café & Bar", "", "_", "plain", "日本語", "a.b", "x-y"];
+    for encoding in [Encoding::Hex, Encoding::Base32, Encoding::Escape] {
+        let path = Ltree::from_segments(names, encoding).unwrap();
+        assert_eq!(path.nlevel(), names.len());
+        assert!(path
+            .0
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'));
+        assert_eq!(
+            path.decode_segments(encoding),
+            Ok(names.iter().map(|name| name.to_string()).collect())
+        );
+    }
+
+    assert_eq!(Encoding::Hex.encode("Café"), "436166c3a9");
+    assert_eq!(Encoding::Base32.encode("foobar"), "MZXW6YTBOI");
+    assert_eq!(
+        Encoding::Escape.encode("Café & Bar"),
+        "Caf_c3_a9_20_26_20Bar"
+    );
+    assert_eq!(Encoding::Escape.encode("a_b"), "a_5fb");
+    assert_eq!(Encoding::Slugify.encode("  Café & Bar!"), "caf_bar");
+    assert_eq!(Encoding::Slugify.encode("&"), "_");
+    assert_eq!(
+        Encoding::Slugify.decode("caf_bar"),
+        Ok("caf bar".to_string())
+    );
+
+    assert_eq!(
+        Encoding::Hex.decode("4G"),
+        Err(DecodeError::InvalidCharacter {
+            offset: 1,
+            character: 'G'
+        })
+    );
+    assert_eq!(Encoding::Hex.decode("436"), Err(DecodeError::Truncated));
+    assert_eq!(Encoding::Hex.decode("c3"), Err(DecodeError::InvalidUtf8));
+    assert_eq!(Encoding::Base32.decode("MZX"), Err(DecodeError::Truncated));
+    assert_eq!(
+        Encoding::Base32.decode("mzxw"),
+        Err(DecodeError::InvalidCharacter {
+            offset: 0,
+            character: 'm'
+        })
+    );
+    assert_eq!(Encoding::Base32.decode("MY"), Ok("f".to_string()));
+    assert_eq!(
+        Encoding::Base32.decode("MZ"),
+        Err(DecodeError::InvalidCharacter {
+            offset: 1,
+            character: 'Z'
+        })
+    );
+    assert_eq!(Encoding::Escape.decode("a_5"), Err(DecodeError::Truncated));
+    assert_eq!(
+        Encoding::Slugify.decode("Caf"),
+        Err(DecodeError::InvalidCharacter {
+            offset: 0,
+            character: 'C'
+        })
+    );
+    assert_eq!(
+        Ltree("caf_bar.x".to_string())
+            .first()
+            .map(|label| label.decode(Encoding::Slugify)),
+        Some(Ok("caf bar".to_string()))
+    );
+
+    let long = "é".repeat(MAX_LABEL_LENGTH);
+    assert_eq!(
+        Encoding::Slugify
+            .encode(&"a ".repeat(MAX_LABEL_LENGTH))
+            .len(),
+        MAX_LABEL_LENGTH - 1
+    );
+    assert!(matches!(
+        Ltree::from_segments([long.as_str()], Encoding::Base32),
+        Err(LtreeParseError::LabelTooLong { .. })
+    ));
+}
+
+#[test]
+fn label_encodings_agree_with_postgres() {
+    let mut connection = get_connection();
+
+    let names = ["Café & Bar", "", "_", "日本語", "a.b", "x-y", "Ünïcødé ✓"];
+    for encoding in [
+        Encoding::Hex,
+        Encoding::Base32,
+        Encoding::Escape,
+        Encoding::Slugify,
+    ] {
+        let path = Ltree::from_segments(names, encoding).unwrap();
+        let theirs =
+            select(ltree2text(text2ltree(path.0.as_str()))).get_result::<String>(&mut connection);
+        assert_eq!(theirs, Ok(path.0.clone()), "rejected {:?}", encoding);
+    }
+}
+
+#[cfg(feature = "postgres-types")]
+#[test]
+fn postgres_types_round_trip() {
+    dotenv::dotenv().ok();
+
+    let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let mut client = postgres::Client::connect(&database_url, postgres::NoTls)
+        .expect("Error connecting to TEST_DATABASE_URL");
+
+    let path = Ltree("root.bacteria".to_string());
+    let query = Lquery("*.aquificae|thermotogae".to_string());
+    let text_query = Ltxtquery("aquificae & !archaea".to_string());
+    let row = client
+        .query_one(
+            "SELECT $1::ltree, $2::lquery, $3::ltxtquery",
+            &[&path, &query, &text_query],
+        )
+        .unwrap();
+    assert_eq!(row.get::<_, Ltree>(0), path);
+    assert_eq!(row.get::<_, Lquery>(1), query);
+    assert_eq!(row.get::<_, Ltxtquery>(2), text_query);
+
+    let paths = client
+        .query(
+            "SELECT path FROM my_tree WHERE path <@ $1 AND path ~ $2 ORDER BY path",
+            &[&path, &query],
+        )
+        .unwrap()
+        .into_iter()
+        .map(|row| row.get::<_, Ltree>(0).0)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec!["root.bacteria.aquificae", "root.bacteria.thermotogae"]
+    );
+
+    let row = client
+        .query_one(
+            "SELECT array_agg(path ORDER BY path), NULL::ltree FROM my_tree WHERE path @ $1",
+            &[&text_query],
+        )
+        .unwrap();
+    assert_eq!(
+        row.get::<_, Vec<Ltree>>(0),
+        vec![Ltree("root.bacteria.aquificae".to_string())]
+    );
+    assert_eq!(row.get::<_, Option<Ltree>>(1), None);
+
+    // The ltree value is refused before it is sent as an lquery.
+    let error = client.query_one("SELECT $1::lquery", &[&path]).unwrap_err();
+    let source = std::error::Error::source(&error).map(|e| e.to_string());
+    assert!(source.is_some_and(|e| e.contains("cannot convert")));
+}
+
+#[cfg(feature = "sqlx")]
+#[test]
+fn sqlx_round_trip() {
+    use sqlx::{Connection as _, Row as _};
+
+    dotenv::dotenv().ok();
+
+    let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let mut connection = sqlx::PgConnection::connect(&database_url)
+            .await
+            .expect("Error connecting to TEST_DATABASE_URL");
+
+        let path = Ltree("root.bacteria".to_string());
+        let query = Lquery("*.aquificae|thermotogae".to_string());
+        let text_query = Ltxtquery("aquificae & !archaea".to_string());
+        let row = sqlx::query("SELECT $1::ltree, $2::lquery, $3::ltxtquery")
+            .bind(&path)
+            .bind(&query)
+            .bind(&text_query)
+            .fetch_one(&mut connection)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<Ltree, _>(0), path);
+        assert_eq!(row.get::<Lquery, _>(1), query);
+        assert_eq!(row.get::<Ltxtquery, _>(2), text_query);
+
+        let paths = vec![
+            Ltree("root.archaea".to_string()),
+            Ltree("root.bacteria".to_string()),
+            Ltree("root.none".to_string()),
+        ];
+        let found = sqlx::query_scalar::<_, Vec<Ltree>>(
+            "SELECT array_agg(path ORDER BY path) FROM my_tree WHERE path = ANY($1)",
+        )
+        .bind(&paths)
+        .fetch_one(&mut connection)
+        .await;
+        assert_eq!(found.ok(), Some(paths[..2].to_vec()));
+
+        let found = sqlx::query_scalar::<_, Ltree>(
+            "SELECT path FROM my_tree WHERE path ? $1 ORDER BY path",
+        )
+        .bind(vec![
+            Lquery("*.plantae".to_string()),
+            Lquery("*.aquificae".to_string()),
+        ])
+        .fetch_all(&mut connection)
+        .await
+        .map(|paths| paths.into_iter().map(|p| p.0).collect::<Vec<_>>());
+        assert_eq!(
+            found.ok(),
+            Some(vec![
+                "root.bacteria.aquificae".to_string(),
+                "root.eukaryota.plantae".to_string()
+            ])
+        );
+
+        // Queries without parameters return text.
+        let row = sqlx::raw_sql("SELECT 'root.archaea'::ltree")
+            .fetch_one(&mut connection)
+            .await
+            .unwrap();
+        assert_eq!(row.get::<Ltree, _>(0), Ltree("root.archaea".to_string()));
+    });
+}
+
+#[test]
+fn query_values() {
+    let mut connection = get_connection();
+
+    let result = select((
+        text2ltree("foo_bar_baz").matches(Lquery("foo_bar%".to_string())),
+        text2ltree("foo_barbaz").matches(Lquery("foo_bar%".to_string())),
+        text2ltree("foo_bar_baz").matches_any(vec![
+            Lquery("foo_bat%".to_string()),
+            Lquery("foo_bar%".to_string()),
+        ]),
+        text2ltree("Russian.Hello.Europe")
+            .tmatches(Ltxtquery("Europe & Russia*@ & !Transportation".to_string())),
+    ))
+    .get_result::<(bool, bool, bool, bool)>(&mut connection);
+    assert_eq!(result, Ok((true, false, true, true)));
+
+    let result = select((lquery("*.foo{1,}"), ltxtquery("Europe & !Russia")))
+        .get_result::<(Lquery, Ltxtquery)>(&mut connection);
+    assert_eq!(
+        result,
+        Ok((
+            Lquery("*.foo{1,}".to_string()),
+            Ltxtquery("Europe & !Russia".to_string())
+        ))
+    );
+}
+
+#[test]
+fn lquery_ast() {
+    let query = lq::Query::new(vec![
+        Level::label("Top"),
+        Level::any().repeat(Quantifier::between(1, 2)),
+        Level::one_of(vec![
+            Variant::new("europe").ci(),
+            Variant::new("asia").prefix().word(),
+        ]),
+        Level::label("Russia").negate(),
+        Level::any(),
+    ]);
+    assert_eq!(query.to_string(), "Top.*{1,2}.europe@|asia%*.!Russia.*");
+    assert_eq!(lq::Query::parse(&query.to_string()), Ok(query.clone()));
+    assert_eq!(
+        Lquery::from(query),
+        Lquery("Top.*{1,2}.europe@|asia%*.!Russia.*".to_string())
+    );
+
+    assert_eq!(
+        lq::Query::parse("a.b@*x"),
+        Err(lq::ParseError::Unexpected {
+            offset: 5,
+            found: Some('x'),
+            expected: Expected::LabelDelimiter
+        })
+    );
+    assert_eq!(
+        lq::Query::parse("a.!"),
+        Err(lq::ParseError::Unexpected {
+            offset: 3,
+            found: None,
+            expected: Expected::Label
+        })
+    );
+    assert_eq!(
+        lq::Query::parse("*{3,1}"),
+        Err(lq::ParseError::BoundsOutOfOrder {
+            offset: 1,
+            low: 3,
+            high: 1
+        })
+    );
+    assert_eq!(lq::Query::parse("*{70000}").map_err(|e| e.offset()), Err(1));
+}
+
+#[test]
+fn lquery_ast_agrees_with_postgres() {
+    let mut connection = get_connection();
+
+    let inputs = [
+        "*",
+        "a",
+        "a.b.c",
+        "*.a.*",
+        "*{0,}",
+        "*{,}",
+        "*{2}",
+        "*{2,}",
+        "*{,3}",
+        "*{1,3}",
+        "*{0,65535}",
+        "*{65535}",
+        "a{,}",
+        "a{1}",
+        "a{0,65535}",
+        "a@*%|B%.!c",
+        "a%%@@**",
+        "!a|b{1,2}",
+        "foo_bar%*.baz",
+        "",
+        ".",
+        "a.",
+        "a..b",
+        "!*",
+        "*a",
+        "a@b",
+        "a|",
+        "a{",
+        "a{1",
+        "a{1,",
+        "a{1,2",
+        "a{1}b",
+        "a{x}",
+        "*{3,1}",
+        "*{70000}",
+        "a b",
+    ];
+    for input in inputs {
+        let ours = lq::Query::parse(input);
+        let theirs = connection.transaction::<Lquery, diesel::result::Error, _>(|conn| {
+            select(lquery(input)).get_result(conn)
+        });
+        match (ours, theirs) {
+            (Ok(ours), Ok(theirs)) => assert_eq!(Lquery::from(ours), theirs, "{:?}", input),
+            (Err(_), Err(_)) => {}
+            (ours, theirs) => panic!("disagreement on {:?}: {:?} {:?}", input, ours, theirs),
+        }
+    }
+}
+
+#[test]
+fn ltxtquery_ast() {
+    let query = Txt::word("Europe").ci() & !Txt::word("Russia");
+    assert_eq!(query.to_string(), "Europe@ & !Russia");
+    assert_eq!(Txt::parse("Europe@&!Russia"), Ok(query));
+
+    let query = (Txt::word("a") | Txt::word("b").prefix()).word_match()
+        & !(Txt::word("c") & Txt::word("d"));
+    assert_eq!(query.to_string(), "( a% | b%* ) & !( c & d )");
+    assert_eq!(Txt::parse(&query.to_string()), Ok(query.clone()));
+    assert_eq!(
+        Ltxtquery::from(query),
+        Ltxtquery("( a% | b%* ) & !( c & d )".to_string())
+    );
+
+    assert_eq!(
+        Txt::parse("a@b"),
+        Err(ltq::ParseError::Unexpected {
+            offset: 2,
+            found: Some('b'),
+            expected: ltq::Expected::Modifier
+        })
+    );
+    assert_eq!(
+        Txt::parse("(a & b"),
+        Err(ltq::ParseError::Unexpected {
+            offset: 6,
+            found: None,
+            expected: ltq::Expected::CloseParen
+        })
+    );
+    assert_eq!(Txt::parse("a & & b").map_err(|e| e.offset()), Err(4));
+}
+
+#[test]
+fn ltxtquery_ast_agrees_with_postgres() {
+    let mut connection = get_connection();
+
+    let inputs = [
+        "a",
+        " a ",
+        "a  &  b",
+        "a\t& b",
+        "\ta & b",
+        "a & b | c",
+        "a | b | c",
+        "a | (b | c)",
+        "!(a|b)",
+        "!(a&b)&c",
+        "!!a",
+        "a%@*",
+        "a@@",
+        "a@b",
+        "()",
+        "(a",
+        "a)",
+        "a & ",
+        "",
+        "a & (b | c) & d",
+        "!a | !(b)",
+        "Europe & Russia*@ & !Transportation",
+        "a|b&c|d",
+        "((a))",
+        "!(!a | b) & (c | !d)",
+        "a b",
+        "a!",
+        "a & !",
+        "foo_bar%",
+    ];
+    for input in inputs {
+        let ours = Txt::parse(input);
+        let theirs = connection.transaction::<Ltxtquery, diesel::result::Error, _>(|conn| {
+            select(ltxtquery(input)).get_result(conn)
+        });
+        match (ours, theirs) {
+            (Ok(ours), Ok(theirs)) => assert_eq!(Ltxtquery::from(ours), theirs, "{:?}", input),
+            (Err(_), Err(_)) => {}
+            (ours, theirs) => panic!("disagreement on {:?}: {:?} {:?}", input, ours, theirs),
+        }
+    }
+
+    let long = "x".repeat(1001);
+    assert_eq!(Txt::parse(&long).map_err(|e| e.offset()), Err(0));
+}
+
+#[test]
+fn lquery_is_match() {
+    let path = Ltree("Top.Countries.Europe.Russia".to_string());
+    let query = |s: &str| lq::Query::parse(s).unwrap();
+
+    assert!(query("Top.*.Russia").is_match(&path));
+    assert!(query("*.Europe.*").is_match(&path));
+    assert!(!query("*.Europe").is_match(&path));
+    assert!(query("top@.*{2}.russ@*").is_match(&path));
+    assert!(!query("Top.*{3}.Russia").is_match(&path));
+    assert!(query("Top.!Asia|America.*").is_match(&path));
+    assert!(lq::Query::is_match_any(
+        &[query("Asia"), query("*.Russia")],
+        &path
+    ));
+    assert!(Lquery("*.Europe.*".to_string()).is_match(&path));
+    assert!(!Lquery("*.Europe.".to_string()).is_match(&path));
+}
+
+#[test]
+fn lquery_is_match_agrees_with_postgres() {
+    let mut connection = get_connection();
+
+    let paths = [
+        "",
+        "a",
+        "a.b",
+        "a.b.c",
+        "a.a.a.b",
+        "foo_bar_baz",
+        "Foo_Bar.baz",
+        "foo1_bar2_baz.x",
+        "x.foo_barbaz",
+        "Top.Countries.Europe.Russia",
+    ];
+    let queries = [
+        "*",
+        "a",
+        "a.*",
+        "*.a",
+        "*.a.*",
+        "a.b.c",
+        "*{2}",
+        "*{1,2}",
+        "*{,1}",
+        "*{2,}.c",
+        "a{2,}.b",
+        "a{,2}.*",
+        "!a",
+        "!a.*",
+        "*.!b.*",
+        "!a|b.*{1,}",
+        "a|b.b|c.*",
+        "A@.*",
+        "a*.*",
+        "fo*.*",
+        "foo_bar%",
+        "foo_bar%*",
+        "bar_foo%.*",
+        "FOO_BAR%@",
+        "bar%*@.*",
+        "foo%.*",
+        "_%.*",
+        "*.c{0}",
+        "*{0}.a",
+        "a.*{0,1}.b.*",
+        "*.europe@.*",
+        "Top.*.Russia",
+        "*{1}.*{1}.!Asia|Russ*",
+        "!Foo_Bar.*",
+        "!fo*{1,}.*",
+    ];
+
+    for path in paths {
+        let ours = Ltree(path.to_string());
+        for query in queries {
+            let theirs = select(text2ltree(path).matches(lquery(query)))
+                .get_result::<bool>(&mut connection)
+                .unwrap();
+            let parsed = lq::Query::parse(query).unwrap();
+            assert_eq!(parsed.is_match(&ours), theirs, "{:?} ~ {:?}", path, query);
+        }
+
+        for pair in queries.windows(2) {
+            let theirs =
+                select(text2ltree(path).matches_any(array((lquery(pair[0]), lquery(pair[1])))))
+                    .get_result::<bool>(&mut connection)
+                    .unwrap();
+            let parsed = pair
+                .iter()
+                .map(|q| lq::Query::parse(q).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(lq::Query::is_match_any(&parsed, &ours), theirs);
+        }
+    }
+}
+
+#[test]
+fn ltxtquery_is_match() {
+    let query = Txt::parse("Europe & Russia*@ & !Transportation").unwrap();
+
+    assert!(query.is_match(&Ltree("Russian.Hello.Europe".to_string())));
+    assert!(!query.is_match(&Ltree("Europe.russia.Transportation".to_string())));
+    assert!(query.is_match(&Ltree("russians.today.Europe".to_string())));
+    assert!(Ltxtquery("a & b".to_string()).is_match(&Ltree("a.b.c".to_string())));
+    assert!(!Ltxtquery("a & ".to_string()).is_match(&Ltree("a.b.c".to_string())));
+}
+
+#[test]
+fn ltxtquery_is_match_agrees_with_postgres() {
+    let mut connection = get_connection();
+
+    let paths = [
+        "",
+        "a",
+        "a.b.c",
+        "foo_bar_baz.x",
+        "Foo_Bar.baz",
+        "Russian.Hello.Europe",
+        "Europe.russia.Transportation",
+    ];
+    let queries = [
+        "a",
+        "!a",
+        "a & b",
+        "a | z",
+        "!(a | b)",
+        "a & !c",
+        "A@",
+        "fo*",
+        "FOO*@",
+        "bar%",
+        "bar_foo%",
+        "ba%*",
+        "BAR_baz%@*",
+        "Europe & Russia*@ & !Transportation",
+        "( a | x ) & ( b | y ) & !z",
+    ];
+
+    for path in paths {
+        for query in queries {
+            let theirs = select(text2ltree(path).tmatches(ltxtquery(query)))
+                .get_result::<bool>(&mut connection)
+                .unwrap();
+            assert_eq!(
+                Txt::parse(query)
+                    .unwrap()
+                    .is_match(&Ltree(path.to_string())),
+                theirs,
+                "{:?} @ {:?}",
+                path,
+                query
+            );
+        }
+    }
+}
+
+#[test]
+fn ltree_ordering_agrees_with_postgres() {
+    let mut connection = get_connection();
+
+    diesel::delete(my_tree::table)
+        .execute(&mut connection)
+        .unwrap();
+    let mut paths = [
+        "", "a", "A", "a.b", "a_b", "ab", "a0", "a.b.c", "a.B", "b", "a.b_c", "a.bc", "aa.a",
+        "Z.z", "_", "a._",
+    ]
+    .map(|p| Ltree(p.to_string()));
+    diesel::insert_into(my_tree::table)
+        .values(
+            paths
+                .iter()
+                .map(|p| my_tree::path.eq(p))
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut connection)
+        .unwrap();
+
+    let theirs = my_tree::table
+        .select(my_tree::path)
+        .order(my_tree::path)
+        .load::<Ltree>(&mut connection)
+        .unwrap();
+    paths.sort();
+    assert_eq!(paths.to_vec(), theirs);
+    assert!(Ltree("a.b".to_string()) < Ltree("a-b".to_string()));
+}
+
+#[test]
+fn binary_protocol_errors() {
+    use crate::values::{decode_binary, encode_binary, WireFormatError};
+
+    let mut buf = Vec::new();
+    encode_binary("a.b", &mut buf).unwrap();
+    assert_eq!(buf, b"\x01a.b");
+    assert_eq!(decode_binary(&buf), Ok("a.b"));
+    assert_eq!(decode_binary(b"\x01"), Ok(""));
+
+    assert_eq!(decode_binary(b""), Err(WireFormatError::Truncated));
+    assert_eq!(
+        decode_binary(b"\x02a.b"),
+        Err(WireFormatError::UnsupportedVersion(2))
+    );
+    assert_eq!(
+        decode_binary(b"\x01a.\xffb"),
+        Err(WireFormatError::InvalidUtf8 { offset: 2 })
+    );
+}
+
+#[test]
+fn nullable_operators() {
+    use crate::sql_types;
+    use diesel::sql_types::Nullable;
+
+    let mut connection = get_connection();
+    let path = text2ltree("a.b").nullable();
+    let null = || None::<Ltree>.into_sql::<Nullable<sql_types::Ltree>>();
+
+    let result = select((
+        path.contains(Ltree("a.b.c".to_string())),
+        path.contained_by(Ltree("a".to_string())),
+        null().contains(Ltree("a".to_string())),
+        path.matches(Lquery("a.*".to_string())),
+        null().matches(Lquery("a.*".to_string())),
+        path.tmatches(Ltxtquery("b".to_string())),
+        path.matches_any(vec![Lquery("x".to_string()), Lquery("a.b".to_string())]),
+    ))
+    .get_result::<(
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+    )>(&mut connection);
+    assert_eq!(
+        result,
+        Ok((
+            Some(true),
+            Some(true),
+            None,
+            Some(true),
+            None,
+            Some(true),
+            Some(true)
+        ))
+    );
+
+    let result = select((
+        path.concat(Ltree("c".to_string())),
+        null().concat(Ltree("c".to_string())),
+    ))
+    .get_result::<(Option<Ltree>, Option<Ltree>)>(&mut connection);
+    assert_eq!(result, Ok((Some(Ltree("a.b.c".to_string())), None)));
+
+    let paths = array((text2ltree("a"), text2ltree("a.b.c"))).nullable();
+    let null_paths = None::<Vec<Ltree>>.into_sql::<Nullable<Array<sql_types::Ltree>>>();
+    let null_query = None::<Lquery>.into_sql::<Nullable<sql_types::Lquery>>();
+    let result = select((
+        paths.any_contains(Ltree("a.b".to_string())),
+        null_paths.any_matches(Lquery("a.*".to_string())),
+        lquery("a.*").nullable().matches(Ltree("a.b".to_string())),
+        null_query.matches(Ltree("a.b".to_string())),
+        ltxtquery("a").nullable().tmatches(Ltree("a.b".to_string())),
+    ))
+    .get_result::<(
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+    )>(&mut connection);
+    assert_eq!(result, Ok((Some(true), None, Some(true), None, Some(true))));
 }
@@ -0,0 +1,759 @@
+use diesel::dsl::array;
+use diesel::prelude::*;
+use diesel::select;
+use diesel::sql_types::{Array, Text};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use std::env;
+
+use super::schema::{categories, my_tree};
+use crate::adjacency::{AdjacencyError, ColumnLabels, CustomLabels, IdLabels};
+use crate::asynchronous::adjacency;
+use crate::asynchronous::subtree::{copy_subtree, delete_subtree, move_subtree};
+use crate::subtree::{DeleteMode, SubtreeError};
+use crate::{
+    hash_ltree, hash_ltree_extended, index, index_of, lca, lca2, lca3, lca4, lca5, lca6, lca7,
+    lca8, lquery, ltree2text, ltree2text_nullable, ltxtquery, nlevel, subltree, subpath,
+    subpath_from, text2ltree, text2ltree_nullable, Lquery, LqueryArrayExtensions, LqueryExtensions,
+    Ltree, LtreeArrayExtensions, LtreeExtensions, Ltxtquery, LtxtqueryArrayExtensions,
+    LtxtqueryExtensions, TextLtreeExtensions,
+};
+
+async fn get_connection() -> AsyncPgConnection {
+    dotenv::dotenv().ok();
+
+    let database_url = env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL must be set");
+    let mut connection = AsyncPgConnection::establish(&database_url)
+        .await
+        .expect("Error connecting to TEST_DATABASE_URL");
+    connection
+        .begin_test_transaction()
+        .await
+        .expect("couldn't begin test transaction");
+    connection
+}
+
+async fn paths(conn: &mut AsyncPgConnection, under: &str) -> QueryResult<Vec<String>> {
+    my_tree::table
+        .select(my_tree::path)
+        .filter(my_tree::path.contained_by(Ltree(under.to_string())))
+        .order(my_tree::path)
+        .load::<Ltree>(conn)
+        .await
+        .map(|paths| paths.into_iter().map(|p| p.0).collect())
+}
+
+#[tokio::test]
+async fn base_operations() {
+    let mut connection = get_connection().await;
+
+    let results = my_tree::table
+        .select(my_tree::path)
+        .filter(
+            my_tree::path
+                .contained_by(text2ltree("root.eukaryota.plantae"))
+                .or(my_tree::path.contains(text2ltree("root.bacteria"))),
+        )
+        .order(my_tree::id)
+        .load::<Ltree>(&mut connection)
+        .await;
+
+    assert_eq!(
+        results,
+        Ok(vec![
+            Ltree("root".to_string()),
+            Ltree("root.bacteria".to_string()),
+            Ltree("root.eukaryota.plantae".to_string()),
+            Ltree("root.eukaryota.plantae.nematophyta".to_string()),
+            Ltree("root.eukaryota.plantae.chlorophyta".to_string())
+        ])
+    );
+}
+
+#[tokio::test]
+async fn functions() {
+    let mut connection = get_connection().await;
+
+    let result = select(ltree2text(subltree(text2ltree("Top.Child1.Child2"), 1, 2)))
+        .get_result::<String>(&mut connection)
+        .await;
+    assert_eq!(result, Ok("Child1".into()));
+
+    let result = select(ltree2text(subpath(text2ltree("Top.Child1.Child2"), 0, 2)))
+        .get_result::<String>(&mut connection)
+        .await;
+    assert_eq!(result, Ok("Top.Child1".into()));
+
+    let result = select(nlevel(text2ltree("Top.Child1.Child2")))
+        .get_result::<i32>(&mut connection)
+        .await;
+    assert_eq!(result, Ok(3));
+
+    let result = select(index(
+        text2ltree("0.1.2.3.5.4.5.6.8.5.6.8"),
+        text2ltree("5.6"),
+        0,
+    ))
+    .get_result::<i32>(&mut connection)
+    .await;
+    assert_eq!(result, Ok(6));
+
+    let result = select(ltree2text(lca(array((
+        text2ltree("1.2.2.3"),
+        text2ltree("1.2.3"),
+    )))))
+    .get_result::<String>(&mut connection)
+    .await;
+    assert_eq!(result, Ok("1.2".into()));
+
+    // `hash_ltree` needs ltree 1.3, from PostgreSQL 16.
+    let has_hash = diesel::sql_query("SELECT 1 FROM pg_proc WHERE proname = 'hash_ltree'")
+        .execute(&mut connection)
+        .await;
+    if has_hash == Ok(1) {
+        let result = select((
+            hash_ltree(text2ltree("a.b")),
+            hash_ltree(text2ltree("a.b")),
+            hash_ltree_extended(text2ltree("a.b"), 42),
+            hash_ltree_extended(text2ltree("a.b"), 42),
+        ))
+        .get_result::<(i32, i32, i64, i64)>(&mut connection)
+        .await;
+        let (hash, again, extended, extended_again) = result.unwrap();
+        assert_eq!(hash, again);
+        assert_eq!(extended, extended_again);
+    }
+}
+
+#[tokio::test]
+async fn function_overloads() {
+    let mut connection = get_connection().await;
+
+    let result = select(ltree2text(subpath_from(text2ltree("Top.Child1.Child2"), 1)))
+        .get_result::<String>(&mut connection)
+        .await;
+    assert_eq!(result, Ok("Child1.Child2".into()));
+
+    let result = select(index_of(text2ltree("0.1.2.3.5.4.5.6"), text2ltree("5.6")))
+        .get_result::<i32>(&mut connection)
+        .await;
+    assert_eq!(result, Ok(6));
+
+    let result = select((
+        text2ltree_nullable(Some("a.b")),
+        text2ltree_nullable(None::<String>),
+        ltree2text_nullable(None::<Ltree>),
+    ))
+    .get_result::<(Option<Ltree>, Option<Ltree>, Option<String>)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((Some(Ltree("a.b".to_string())), None, None)));
+
+    let result = select((
+        ltree2text(lca2(text2ltree("1.2.3"), text2ltree("1.2.4"))),
+        ltree2text(lca3(
+            text2ltree("1.2.3"),
+            text2ltree("1.2.4"),
+            text2ltree("1.5"),
+        )),
+        ltree2text(lca8(
+            text2ltree("1.2.3"),
+            text2ltree("1.2.4"),
+            text2ltree("1.2.5"),
+            text2ltree("1.2.6"),
+            text2ltree("1.2.7"),
+            text2ltree("1.2.8"),
+            text2ltree("1.2.9"),
+            text2ltree("1.2.3.4"),
+        )),
+    ))
+    .get_result::<(String, String, String)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok(("1.2".into(), "1".into(), "1.2".into())));
+
+    let result = select((
+        ltree2text(lca4(
+            text2ltree("1.2.3"),
+            text2ltree("1.2.4"),
+            text2ltree("1.2.5"),
+            text2ltree("1.3"),
+        )),
+        ltree2text(lca5(
+            text2ltree("1.2.3"),
+            text2ltree("1.2.4"),
+            text2ltree("1.2.5"),
+            text2ltree("1.2.6"),
+            text2ltree("1.2.7"),
+        )),
+        ltree2text(lca6(
+            text2ltree("a.b.c"),
+            text2ltree("a.b.d"),
+            text2ltree("a.b.e"),
+            text2ltree("a.b.f"),
+            text2ltree("a.b.g"),
+            text2ltree("a.b.c.d"),
+        )),
+        ltree2text(lca7(
+            text2ltree("x.y.a"),
+            text2ltree("x.y.b"),
+            text2ltree("x.y.c"),
+            text2ltree("x.y.d"),
+            text2ltree("x.y.e"),
+            text2ltree("x.y.f"),
+            text2ltree("x.z"),
+        )),
+    ))
+    .get_result::<(String, String, String, String)>(&mut connection)
+    .await;
+    assert_eq!(
+        result,
+        Ok(("1".into(), "1.2".into(), "a.b".into(), "x".into()))
+    );
+}
+
+#[tokio::test]
+async fn operators() {
+    let mut connection = get_connection().await;
+
+    let result = select((
+        text2ltree("1.1").eq(text2ltree("1.2")),
+        text2ltree("1.1").eq(text2ltree("1.1")),
+        text2ltree("1.1").ne(text2ltree("1.2")),
+        text2ltree("1.1").ne(text2ltree("1.1")),
+    ))
+    .get_result::<(bool, bool, bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((false, true, true, false)));
+
+    let result = select((
+        text2ltree("1").lt(text2ltree("1.1")),
+        text2ltree("1.2").gt(text2ltree("1.1")),
+        text2ltree("1.2").le(text2ltree("1.1")),
+        text2ltree("1.2.1").ge(text2ltree("1.2")),
+    ))
+    .get_result::<(bool, bool, bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, true, false, true)));
+
+    let result = select((
+        text2ltree("foo_bar_baz").matches(lquery("foo_bar%")),
+        text2ltree("foo_barbaz").matches(lquery("foo_bar%")),
+        lquery("foo_bar%*").matches(text2ltree("foo1_bar2_baz")),
+        lquery("foo_bar%*").matches(text2ltree("foo1_br2_baz")),
+    ))
+    .get_result::<(bool, bool, bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, false, true, false)));
+
+    let result = select((
+        text2ltree("foo_bar_baz").matches_any(array((lquery("foo_bar%"), lquery("foo_bat%")))),
+        text2ltree("foo_bar_baz").matches_any(array((lquery("foo_bat%"),))),
+    ))
+    .get_result::<(bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, false)));
+
+    let result = select((
+        array((lquery("foo_bar%"), lquery("foo_bat%"))).any_matches(text2ltree("foo_bar_baz")),
+        array((lquery("foo_bat%"),)).any_matches(text2ltree("foo_bar_baz")),
+    ))
+    .get_result::<(bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, false)));
+
+    let q = ltxtquery("Europe & Russia*@ & !Transportation");
+    let result = select((
+        text2ltree("Russian.Hello.Europe").tmatches(q),
+        q.tmatches(text2ltree("Europe.russia.Transportation")),
+        q.tmatches(text2ltree("russians.today.Europe")),
+    ))
+    .get_result::<(bool, bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, false, true)));
+
+    let result = select(ltree2text(text2ltree("a.b").concat(text2ltree("c.d"))))
+        .get_result::<String>(&mut connection)
+        .await;
+    assert_eq!(result, Ok("a.b.c.d".into()));
+
+    let result = select((
+        text2ltree("a.b").contained_by_any(array((text2ltree("a"), text2ltree("a.b.c")))),
+        array((text2ltree("a"), text2ltree("a.b.c"))).any_contains(text2ltree("a.b")),
+        text2ltree("a.b").contains_any(array((text2ltree("a"), text2ltree("a.b.c")))),
+        array((text2ltree("a"), text2ltree("a.b.c"))).any_contained_by(text2ltree("a.b")),
+    ))
+    .get_result::<(bool, bool, bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, true, true, true)));
+
+    let result = select((
+        array((text2ltree("a"), text2ltree("a.b"))).any_matches(lquery("a%")),
+        lquery("a%").matches_any(array((text2ltree("a"), text2ltree("a.b")))),
+    ))
+    .get_result::<(bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, true)));
+
+    let result = select((
+        array((text2ltree("a"), text2ltree("a.b")))
+            .any_matches_any(array((lquery("a%"), lquery("b%")))),
+        array((lquery("a%"), lquery("b%")))
+            .any_matches_any(array((text2ltree("a"), text2ltree("a.b")))),
+    ))
+    .get_result::<(bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, true)));
+
+    let result = select((
+        array((text2ltree("a"), text2ltree("a.b"))).any_tmatches(ltxtquery("a")),
+        ltxtquery("a").tmatches_any(array((text2ltree("a"), text2ltree("a.b")))),
+    ))
+    .get_result::<(bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, true)));
+
+    let result = select((
+        ltree2text_nullable(
+            array((text2ltree("a.b.c"), text2ltree("a"))).first_contains(text2ltree("a.b")),
+        ),
+        ltree2text_nullable(
+            array((text2ltree("a"), text2ltree("a.b.c"))).first_contained_by(text2ltree("a.b")),
+        ),
+    ))
+    .get_result::<(Option<String>, Option<String>)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((Some("a".into()), Some("a.b.c".into()))));
+
+    let result = select((
+        ltree2text_nullable(
+            array((text2ltree("a.b.c"), text2ltree("a"))).first_matches(lquery("a%")),
+        ),
+        ltree2text_nullable(
+            array((text2ltree("a"), text2ltree("a.b.c"))).first_tmatches(ltxtquery("a & b")),
+        ),
+    ))
+    .get_result::<(Option<String>, Option<String>)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((Some("a".into()), Some("a.b.c".into()))));
+
+    let result = select((
+        array((text2ltree("a"), text2ltree("b"))).first_contains(text2ltree("c.d")),
+        array((text2ltree("a"), text2ltree("b"))).first_matches(lquery("c.*")),
+    ))
+    .get_result::<(Option<Ltree>, Option<Ltree>)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((None, None)));
+
+    let result = select((
+        ltree2text(text2ltree("a.b").concat_text("c.d")),
+        ltree2text("a.b".into_sql::<Text>().concat_ltree(text2ltree("c.d"))),
+    ))
+    .get_result::<(String, String)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok(("a.b.c.d".into(), "a.b.c.d".into())));
+
+    let result = select((
+        text2ltree("a.b").lt(text2ltree("a.b.c")),
+        text2ltree("a.b").le(text2ltree("a.b")),
+        text2ltree("a_b").gt(text2ltree("a.b")),
+        text2ltree("B").ge(text2ltree("a")),
+        array((text2ltree("a"), text2ltree("a.c"))).gt(array((text2ltree("a"), text2ltree("a.b")))),
+    ))
+    .get_result::<(bool, bool, bool, bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, true, true, false, true)));
+}
+
+#[tokio::test]
+async fn array_operations() {
+    use crate::sql_types;
+
+    let mut connection = get_connection().await;
+    let path = || text2ltree("a.b");
+    let paths = || array((text2ltree("x"), text2ltree("a.b")));
+    let lqueries = || array((lquery("x"), lquery("a.*")));
+    let ltxtqueries = || vec![Ltxtquery("x".to_string()), Ltxtquery("a & b".to_string())];
+
+    // `~` and `?`, from each side.
+    let result = select((
+        path().matches(lquery("a.*")),
+        path().matches_any(lqueries()),
+        paths().any_matches(lquery("a.*")),
+        paths().any_matches_any(lqueries()),
+        lquery("a.*").matches(path()),
+        lquery("a.*").matches_any(paths()),
+        lqueries().any_matches(path()),
+        lqueries().any_matches_any(paths()),
+    ))
+    .get_result::<(bool, bool, bool, bool, bool, bool, bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, true, true, true, true, true, true, true)));
+
+    // `@`, from each side.
+    let result = select((
+        path().tmatches(ltxtquery("a & b")),
+        path().tmatches_any(ltxtqueries()),
+        paths().any_tmatches(ltxtquery("a & b")),
+        paths().any_tmatches_any(ltxtqueries()),
+        ltxtquery("a & b").tmatches(path()),
+        ltxtquery("a & b").tmatches_any(paths()),
+        ltxtqueries()
+            .into_sql::<Array<sql_types::Ltxtquery>>()
+            .any_tmatches(path()),
+        array((ltxtquery("x"), ltxtquery("a & b"))).any_tmatches_any(paths()),
+    ))
+    .get_result::<(bool, bool, bool, bool, bool, bool, bool, bool)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((true, true, true, true, true, true, true, true)));
+
+    let result = select((
+        text2ltree("c").tmatches_any(ltxtqueries()),
+        array((text2ltree("c"),)).any_tmatches_any(ltxtqueries()),
+        path().tmatches_any(Vec::<Ltxtquery>::new()),
+        path().nullable().tmatches_any(None::<Vec<Ltxtquery>>),
+    ))
+    .get_result::<(bool, bool, bool, Option<bool>)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((false, false, false, None)));
+}
+
+#[tokio::test]
+async fn first_match_helpers() {
+    let mut connection = get_connection().await;
+
+    let access_roots = vec![
+        Ltree("root.bacteria".to_string()),
+        Ltree("root.eukaryota.plantae".to_string()),
+        Ltree("root.eukaryota".to_string()),
+    ];
+    let result = my_tree::table
+        .select((my_tree::id, my_tree::path.first_ancestor_in(&access_roots)))
+        .filter(my_tree::id.eq_any([1, 3, 5, 11, 14]))
+        .order(my_tree::id)
+        .load::<(i32, Option<Ltree>)>(&mut connection)
+        .await;
+    assert_eq!(
+        result,
+        Ok(vec![
+            (1, None),
+            (3, Some(Ltree("root.bacteria".to_string()))),
+            (5, None),
+            (11, Some(Ltree("root.eukaryota.plantae".to_string()))),
+            (14, Some(Ltree("root.eukaryota".to_string()))),
+        ])
+    );
+
+    let paths = array((text2ltree("a.b"), text2ltree("a.c"), text2ltree("b.c")));
+    let result = select((
+        text2ltree("a").first_descendant_in(paths),
+        lquery("*.c").first_match_in(paths),
+        ltxtquery("b & c").first_tmatch_in(paths),
+        lquery("x").first_match_in(array((text2ltree("a"),))),
+    ))
+    .get_result::<(Option<Ltree>, Option<Ltree>, Option<Ltree>, Option<Ltree>)>(&mut connection)
+    .await;
+    assert_eq!(
+        result,
+        Ok((
+            Some(Ltree("a.b".to_string())),
+            Some(Ltree("a.c".to_string())),
+            Some(Ltree("b.c".to_string())),
+            None
+        ))
+    );
+}
+
+#[tokio::test]
+async fn nullable_operators() {
+    use crate::sql_types;
+    use diesel::sql_types::Nullable;
+
+    let mut connection = get_connection().await;
+    let path = text2ltree("a.b").nullable();
+    let null = || None::<Ltree>.into_sql::<Nullable<sql_types::Ltree>>();
+
+    let result = select((
+        path.contains(Ltree("a.b.c".to_string())),
+        path.contained_by(Ltree("a".to_string())),
+        null().contains(Ltree("a".to_string())),
+        path.matches(Lquery("a.*".to_string())),
+        null().matches(Lquery("a.*".to_string())),
+        path.tmatches(Ltxtquery("b".to_string())),
+        path.matches_any(vec![Lquery("x".to_string()), Lquery("a.b".to_string())]),
+    ))
+    .get_result::<(
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+    )>(&mut connection)
+    .await;
+    assert_eq!(
+        result,
+        Ok((
+            Some(true),
+            Some(true),
+            None,
+            Some(true),
+            None,
+            Some(true),
+            Some(true)
+        ))
+    );
+
+    let result = select((
+        path.concat(Ltree("c".to_string())),
+        null().concat(Ltree("c".to_string())),
+    ))
+    .get_result::<(Option<Ltree>, Option<Ltree>)>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((Some(Ltree("a.b.c".to_string())), None)));
+
+    let paths = array((text2ltree("a"), text2ltree("a.b.c"))).nullable();
+    let null_paths = None::<Vec<Ltree>>.into_sql::<Nullable<Array<sql_types::Ltree>>>();
+    let null_query = None::<Lquery>.into_sql::<Nullable<sql_types::Lquery>>();
+    let result = select((
+        paths.any_contains(Ltree("a.b".to_string())),
+        null_paths.any_matches(Lquery("a.*".to_string())),
+        lquery("a.*").nullable().matches(Ltree("a.b".to_string())),
+        null_query.matches(Ltree("a.b".to_string())),
+        ltxtquery("a").nullable().tmatches(Ltree("a.b".to_string())),
+    ))
+    .get_result::<(
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+        Option<bool>,
+    )>(&mut connection)
+    .await;
+    assert_eq!(result, Ok((Some(true), None, Some(true), None, Some(true))));
+}
+
+#[tokio::test]
+async fn move_copy_and_delete_subtree() {
+    let mut connection = get_connection().await;
+
+    let result = move_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.plantae".to_string()),
+        &Ltree("root.archaea.flora".to_string()),
+    )
+    .await;
+    assert_eq!(result, Ok(3));
+    assert_eq!(
+        paths(&mut connection, "root.archaea.flora").await,
+        Ok(vec![
+            "root.archaea.flora".to_string(),
+            "root.archaea.flora.chlorophyta".to_string(),
+            "root.archaea.flora.nematophyta".to_string(),
+        ])
+    );
+
+    let result = move_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota".to_string()),
+        &Ltree("root.eukaryota.animalia.eukaryota".to_string()),
+    )
+    .await;
+    assert_eq!(
+        result,
+        Err(SubtreeError::IntoOwnSubtree {
+            from: Ltree("root.eukaryota".to_string()),
+            to: Ltree("root.eukaryota.animalia.eukaryota".to_string()),
+        })
+    );
+
+    let result = copy_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.archaea.flora".to_string()),
+        &Ltree("root.eukaryota.plantae".to_string()),
+        (my_tree::path,),
+        |path| (path,),
+    )
+    .await;
+    assert_eq!(result, Ok(3));
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota.plantae")
+            .await
+            .map(|paths| paths.len()),
+        Ok(3)
+    );
+
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.archaea.flora".to_string()),
+        DeleteMode::Subtree,
+    )
+    .await;
+    assert_eq!(result, Ok(3));
+    assert_eq!(
+        paths(&mut connection, "root.archaea.flora").await,
+        Ok(vec![])
+    );
+
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.plantae".to_string()),
+        DeleteMode::KeepRoot,
+    )
+    .await;
+    assert_eq!(result, Ok(2));
+
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.animalia".to_string()),
+        DeleteMode::ReparentChildren,
+    )
+    .await;
+    assert_eq!(result, Ok(1));
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota").await,
+        Ok(vec![
+            "root.eukaryota".to_string(),
+            "root.eukaryota.chancelloriidae".to_string(),
+            "root.eukaryota.cloudinidae".to_string(),
+            "root.eukaryota.plantae".to_string(),
+        ])
+    );
+
+    diesel::insert_into(my_tree::table)
+        .values(&vec![
+            my_tree::path.eq(Ltree("root.eukaryota.fungi.agaricales".to_string())),
+            my_tree::path.eq(Ltree("root.eukaryota.plantae.cloudinidae".to_string())),
+        ])
+        .execute(&mut connection)
+        .await
+        .unwrap();
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.fungi".to_string()),
+        DeleteMode::ReparentChildren,
+    )
+    .await;
+    assert_eq!(
+        result,
+        Err(SubtreeError::MissingRoot {
+            root: Ltree("root.eukaryota.fungi".to_string())
+        })
+    );
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota.fungi").await,
+        Ok(vec!["root.eukaryota.fungi.agaricales".to_string()])
+    );
+
+    let result = delete_subtree(
+        &mut connection,
+        my_tree::table,
+        my_tree::path,
+        &Ltree("root.eukaryota.plantae".to_string()),
+        DeleteMode::ReparentChildren,
+    )
+    .await;
+    assert_eq!(
+        result,
+        Err(SubtreeError::PathTaken {
+            path: Ltree("root.eukaryota.cloudinidae".to_string())
+        })
+    );
+    assert_eq!(
+        paths(&mut connection, "root.eukaryota.plantae").await,
+        Ok(vec![
+            "root.eukaryota.plantae".to_string(),
+            "root.eukaryota.plantae.cloudinidae".to_string(),
+        ])
+    );
+}
+
+#[tokio::test]
+async fn adjacency_list() {
+    let mut connection = get_connection().await;
+
+    let result: Result<Vec<(i32, Ltree)>, _> = adjacency::dry_run(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        ColumnLabels(categories::slug),
+    )
+    .await;
+    assert_eq!(
+        result.map(|pairs| pairs
+            .into_iter()
+            .map(|(_, path)| path.0)
+            .collect::<Vec<_>>()),
+        Ok(vec![
+            "animals".to_string(),
+            "animals.birds".to_string(),
+            "animals.birds.owls".to_string(),
+            "animals.fish".to_string(),
+            "plants".to_string(),
+            "plants.trees".to_string(),
+        ])
+    );
+
+    let result = adjacency::dry_run(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        CustomLabels::new(|id: &i32| format!("c{}", id)),
+    )
+    .await;
+    assert_eq!(
+        result.map(|pairs| pairs.into_iter().last()),
+        Ok(Some((6, Ltree("c5.c6".to_string()))))
+    );
+
+    diesel::insert_into(categories::table)
+        .values((categories::id.eq(7), categories::parent_id.eq(Some(99))))
+        .execute(&mut connection)
+        .await
+        .unwrap();
+    let result = adjacency::fill(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        IdLabels,
+        categories::path,
+    )
+    .await;
+    assert_eq!(result, Err(AdjacencyError::Orphan { id: 7, parent: 99 }));
+    diesel::delete(categories::table.filter(categories::id.eq(7)))
+        .execute(&mut connection)
+        .await
+        .unwrap();
+
+    let result: Result<_, AdjacencyError<i32>> = adjacency::fill(
+        &mut connection,
+        categories::table,
+        categories::id,
+        categories::parent_id,
+        IdLabels,
+        categories::path,
+    )
+    .await;
+    assert_eq!(result, Ok(6));
+    let filled = categories::table
+        .select(categories::path)
+        .filter(categories::id.eq(4))
+        .get_result::<Option<Ltree>>(&mut connection)
+        .await;
+    assert_eq!(filled, Ok(Some(Ltree("1.2.4".to_string()))));
+}
@@ -6,6 +6,18 @@ pub mod sql_types {
     pub struct Ltree;
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::sql_types::*;
+
+    categories (id) {
+        id -> Int4,
+        parent_id -> Nullable<Int4>,
+        slug -> Nullable<Text>,
+        path -> Nullable<Ltree>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::sql_types::*;
@@ -15,3 +27,5 @@ diesel::table! {
         path -> Ltree,
     }
 }
+
+diesel::allow_tables_to_appear_in_same_query!(categories, my_tree,);
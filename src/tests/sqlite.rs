@@ -0,0 +1,162 @@
+use diesel::dsl::not;
+use diesel::prelude::*;
+use diesel::select;
+use diesel::sqlite::SqliteConnection;
+
+use super::get_connection;
+use super::schema::my_tree;
+use crate::sqlite::{self, register_functions};
+use crate::{
+    index, index_of, lca2, lca3, lquery, ltree2text, nlevel, subltree, subpath, subpath_from,
+    text2ltree, LqueryExtensions, Ltree, LtreeExtensions,
+};
+
+// An in-memory database holding a copy of the Postgres fixture data.
+fn get_sqlite_connection(pg: &mut PgConnection) -> SqliteConnection {
+    let mut connection = SqliteConnection::establish(":memory:").unwrap();
+    register_functions(&mut connection).unwrap();
+    diesel::sql_query(
+        "CREATE TABLE my_tree (id INTEGER PRIMARY KEY NOT NULL, path TEXT NOT NULL COLLATE ltree)",
+    )
+    .execute(&mut connection)
+    .unwrap();
+
+    let rows = my_tree::table
+        .select((my_tree::id, my_tree::path))
+        .load::<(i32, Ltree)>(pg)
+        .unwrap();
+    diesel::insert_into(my_tree::table)
+        .values(
+            rows.iter()
+                .map(|(id, path)| (my_tree::id.eq(id), my_tree::path.eq(path)))
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut connection)
+        .unwrap();
+    connection
+}
+
+#[test]
+fn sqlite_agrees_with_postgres() {
+    let mut pg = get_connection();
+    let mut sqlite = get_sqlite_connection(&mut pg);
+
+    macro_rules! assert_agree {
+        ($query:expr, $ty:ty) => {
+            let theirs = $query.load::<$ty>(&mut pg).unwrap();
+            let ours = $query.load::<$ty>(&mut sqlite).unwrap();
+            assert_eq!(ours, theirs, "{}", stringify!($query));
+        };
+    }
+
+    assert_agree!(
+        my_tree::table
+            .select(my_tree::path)
+            .filter(
+                my_tree::path
+                    .contained_by(text2ltree("root.eukaryota.plantae"))
+                    .or(my_tree::path.contains(text2ltree("root.bacteria"))),
+            )
+            .order(my_tree::id),
+        Ltree
+    );
+    assert_agree!(
+        my_tree::table.select(my_tree::path).order(my_tree::path),
+        Ltree
+    );
+    assert_agree!(
+        my_tree::table
+            .select((
+                my_tree::id,
+                nlevel(my_tree::path),
+                subpath(my_tree::path, 0, 2),
+                subpath_from(my_tree::path, -1),
+                ltree2text(lca2(my_tree::path, text2ltree("root.eukaryota.animalia"))),
+            ))
+            .order(my_tree::id),
+        (i32, i32, Ltree, Ltree, String)
+    );
+
+    // `index` is registered under another name on SQLite.
+    let theirs = my_tree::table
+        .select((
+            index(my_tree::path, text2ltree("thermoprotei"), -3),
+            index_of(my_tree::path, text2ltree("eukaryota.animalia")),
+        ))
+        .order(my_tree::id)
+        .load::<(i32, i32)>(&mut pg);
+    let ours = my_tree::table
+        .select((
+            sqlite::index(my_tree::path, text2ltree("thermoprotei"), -3),
+            sqlite::index_of(my_tree::path, text2ltree("eukaryota.animalia")),
+        ))
+        .order(my_tree::id)
+        .load::<(i32, i32)>(&mut sqlite);
+    assert_eq!(ours, theirs);
+    assert_agree!(
+        my_tree::table
+            .select((
+                subltree(my_tree::path, 1, 2),
+                lca3(my_tree::path, text2ltree("root.archaea.x"), my_tree::path),
+            ))
+            .filter(nlevel(my_tree::path).ge(2))
+            .order(my_tree::id),
+        (Ltree, Ltree)
+    );
+    assert_agree!(
+        my_tree::table
+            .select(my_tree::id)
+            .filter(my_tree::path.matches(lquery("*.thermoprotei.*")))
+            .order(my_tree::id),
+        i32
+    );
+    assert_agree!(
+        my_tree::table
+            .select(my_tree::id)
+            .filter(lquery("root.!bacteria.*").matches(my_tree::path))
+            .filter(not(my_tree::path.contains(text2ltree("root.archaea"))))
+            .order(my_tree::id),
+        i32
+    );
+
+    let result = select((
+        text2ltree("a.b").contains(text2ltree("a.b.c")),
+        text2ltree("a.b").contained_by(text2ltree("a.b.c")),
+        nlevel(text2ltree("")),
+    ))
+    .get_result::<(bool, bool, i32)>(&mut sqlite);
+    assert_eq!(result, Ok((true, false, 0)));
+}
+
+#[test]
+fn collation_orders_like_ltree() {
+    let mut pg = get_connection();
+    let mut sqlite = get_sqlite_connection(&mut pg);
+    let fixture = [
+        "a.b.c", "a_b", "a-b", "ab", "a", "a.b", "b", "a.b-c.d", "a.ba", "A",
+    ];
+    diesel::delete(my_tree::table).execute(&mut sqlite).unwrap();
+    diesel::insert_into(my_tree::table)
+        .values(
+            fixture
+                .iter()
+                .enumerate()
+                .map(|(id, path)| {
+                    (
+                        my_tree::id.eq(id as i32),
+                        my_tree::path.eq(Ltree(path.to_string())),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        )
+        .execute(&mut sqlite)
+        .unwrap();
+
+    let mut expected = fixture.map(|path| Ltree(path.to_string())).to_vec();
+    expected.sort();
+    let ordered = my_tree::table
+        .select(my_tree::path)
+        .order(my_tree::path)
+        .load::<Ltree>(&mut sqlite);
+    assert_eq!(ordered, Ok(expected));
+}
@@ -0,0 +1,280 @@
+//! Nesting flat query results into an in-memory tree.
+//!
+//! A [`Forest`] is built from `(Ltree, T)` pairs, such as the rows returned by
+//! `filter(path.contained_by(root))`, in any order. Each row becomes a
+//! [`Tree`] whose children are the rows below it; a row whose parent was not
+//! loaded is attached to its nearest loaded ancestor instead, and a row with
+//! no loaded ancestor becomes a root.
+//!
+//! ```
+//! use diesel_ltree::tree::Forest;
+//! use diesel_ltree::Ltree;
+//!
+//! let forest: Forest<i32> = vec![
+//!     (Ltree("root.a.b".to_string()), 3),
+//!     (Ltree("root".to_string()), 1),
+//!     (Ltree("root.c".to_string()), 2),
+//! ]
+//! .into_iter()
+//! .collect();
+//!
+//! let root = &forest.roots()[0];
+//! assert_eq!(root.children().len(), 2);
+//! assert_eq!(forest.get(&Ltree("root.a.b".to_string())).map(|t| *t.value()), Some(3));
+//! ```
+use std::collections::VecDeque;
+
+use crate::values::Ltree;
+
+/// A loaded row and the rows nested below it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Tree<T> {
+    path: Ltree,
+    value: T,
+    children: Vec<Tree<T>>,
+}
+
+/// The trees built from a set of rows, ordered by path.
+///
+/// With the `serde` feature, this serializes as a list of its roots, each
+/// with its `path`, `value` and nested `children`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Forest<T> {
+    roots: Vec<Tree<T>>,
+}
+
+impl<T> Tree<T> {
+    /// Creates a tree with no children.
+    pub fn new(path: Ltree, value: T) -> Self {
+        Tree {
+            path,
+            value,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Ltree {
+        &self.path
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn value_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// The trees directly below this one, ordered by path. A child need not
+    /// be exactly one level deeper if the rows between were not loaded.
+    pub fn children(&self) -> &[Tree<T>] {
+        &self.children
+    }
+
+    /// Consumes the tree, returning its path, value and children.
+    pub fn into_parts(self) -> (Ltree, T, Vec<Tree<T>>) {
+        (self.path, self.value, self.children)
+    }
+
+    /// Returns the subtree rooted at `path`, if that row was loaded.
+    pub fn get(&self, path: &Ltree) -> Option<&Tree<T>> {
+        if !self.path.is_ancestor_of(path) {
+            return None;
+        }
+        let mut tree = self;
+        while tree.path != *path {
+            tree = tree.children.iter().find(|c| c.path.is_ancestor_of(path))?;
+        }
+        Some(tree)
+    }
+
+    /// Returns the subtree rooted at `path`, if that row was loaded.
+    pub fn get_mut(&mut self, path: &Ltree) -> Option<&mut Tree<T>> {
+        if !self.path.is_ancestor_of(path) {
+            return None;
+        }
+        let mut tree = self;
+        while tree.path != *path {
+            tree = tree
+                .children
+                .iter_mut()
+                .find(|c| c.path.is_ancestor_of(path))?;
+        }
+        Some(tree)
+    }
+
+    /// Detaches and returns the subtree rooted at `path`, which must be
+    /// strictly below this tree's root.
+    pub fn remove(&mut self, path: &Ltree) -> Option<Tree<T>> {
+        remove_from(&mut self.children, path)
+    }
+
+    /// Iterates over the tree in depth-first order, each tree before its
+    /// children.
+    pub fn depth_first(&self) -> DepthFirst<'_, T> {
+        DepthFirst { stack: vec![self] }
+    }
+
+    /// Iterates over the tree in breadth-first order, level by level.
+    pub fn breadth_first(&self) -> BreadthFirst<'_, T> {
+        BreadthFirst {
+            queue: VecDeque::from([self]),
+        }
+    }
+}
+
+impl<T> Forest<T> {
+    /// Builds a forest from `(path, value)` pairs given in any order.
+    ///
+    /// Rows are attached to their nearest loaded ancestor. Rows with equal
+    /// paths become siblings, in the order they were given.
+    pub fn from_pairs<I>(pairs: I) -> Self
+    where
+        I: IntoIterator<Item = (Ltree, T)>,
+    {
+        let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+        // Ancestors sort before their descendants, so every row's nearest
+        // loaded ancestor is on the stack when the row is reached.
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut parents = Vec::with_capacity(pairs.len());
+        let mut stack: Vec<usize> = Vec::new();
+        for (i, (path, _)) in pairs.iter().enumerate() {
+            while let Some(&top) = stack.last() {
+                let ancestor = &pairs[top].0;
+                if ancestor != path && ancestor.is_ancestor_of(path) {
+                    break;
+                }
+                stack.pop();
+            }
+            parents.push(stack.last().copied());
+            stack.push(i);
+        }
+
+        // Children come after their parent, so building from the back
+        // completes each tree's children before the tree itself is placed.
+        let mut nodes = pairs
+            .into_iter()
+            .map(|(path, value)| Some(Tree::new(path, value)))
+            .collect::<Vec<_>>();
+        let mut roots = Vec::new();
+        for i in (0..nodes.len()).rev() {
+            let mut tree = nodes[i].take().expect("each row is placed once");
+            tree.children.reverse();
+            match parents[i] {
+                Some(parent) => nodes[parent]
+                    .as_mut()
+                    .expect("parents are placed after their children")
+                    .children
+                    .push(tree),
+                None => roots.push(tree),
+            }
+        }
+        roots.reverse();
+        Forest { roots }
+    }
+
+    /// The trees whose rows have no loaded ancestor, ordered by path.
+    pub fn roots(&self) -> &[Tree<T>] {
+        &self.roots
+    }
+
+    pub fn into_roots(self) -> Vec<Tree<T>> {
+        self.roots
+    }
+
+    /// Returns the number of rows in the forest.
+    pub fn len(&self) -> usize {
+        self.depth_first().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+
+    /// Returns the subtree rooted at `path`, if that row was loaded.
+    pub fn get(&self, path: &Ltree) -> Option<&Tree<T>> {
+        self.roots.iter().find_map(|root| root.get(path))
+    }
+
+    /// Returns the subtree rooted at `path`, if that row was loaded.
+    pub fn get_mut(&mut self, path: &Ltree) -> Option<&mut Tree<T>> {
+        self.roots.iter_mut().find_map(|root| root.get_mut(path))
+    }
+
+    /// Detaches and returns the subtree rooted at `path`.
+    pub fn remove(&mut self, path: &Ltree) -> Option<Tree<T>> {
+        remove_from(&mut self.roots, path)
+    }
+
+    /// Iterates over every tree in depth-first order, each tree before its
+    /// children.
+    pub fn depth_first(&self) -> DepthFirst<'_, T> {
+        DepthFirst {
+            stack: self.roots.iter().rev().collect(),
+        }
+    }
+
+    /// Iterates over every tree in breadth-first order, level by level.
+    pub fn breadth_first(&self) -> BreadthFirst<'_, T> {
+        BreadthFirst {
+            queue: self.roots.iter().collect(),
+        }
+    }
+}
+
+fn remove_from<T>(trees: &mut Vec<Tree<T>>, path: &Ltree) -> Option<Tree<T>> {
+    let i = trees.iter().position(|t| t.path.is_ancestor_of(path))?;
+    if trees[i].path == *path {
+        Some(trees.remove(i))
+    } else {
+        remove_from(&mut trees[i].children, path)
+    }
+}
+
+impl<T> FromIterator<(Ltree, T)> for Forest<T> {
+    fn from_iter<I: IntoIterator<Item = (Ltree, T)>>(iter: I) -> Self {
+        Forest::from_pairs(iter)
+    }
+}
+
+impl<T> Default for Forest<T> {
+    fn default() -> Self {
+        Forest { roots: Vec::new() }
+    }
+}
+
+/// Depth-first iterator over a [`Tree`] or [`Forest`].
+#[derive(Debug, Clone)]
+pub struct DepthFirst<'a, T> {
+    stack: Vec<&'a Tree<T>>,
+}
+
+impl<'a, T> Iterator for DepthFirst<'a, T> {
+    type Item = &'a Tree<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tree = self.stack.pop()?;
+        self.stack.extend(tree.children.iter().rev());
+        Some(tree)
+    }
+}
+
+/// Breadth-first iterator over a [`Tree`] or [`Forest`].
+#[derive(Debug, Clone)]
+pub struct BreadthFirst<'a, T> {
+    queue: VecDeque<&'a Tree<T>>,
+}
+
+impl<'a, T> Iterator for BreadthFirst<'a, T> {
+    type Item = &'a Tree<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tree = self.queue.pop_front()?;
+        self.queue.extend(&tree.children);
+        Some(tree)
+    }
+}
@@ -0,0 +1,290 @@
+use std::fmt;
+
+use super::{Label, Ltree, LtreeParseError};
+
+/// How to turn an arbitrary string into a label using only `A-Za-z0-9_`, the
+/// characters every version of the ltree extension accepts.
+///
+/// Every encoding writes the empty string as `_`, which none of them produce
+/// otherwise.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Encoding {
+    /// The UTF-8 bytes as lowercase hex digits, two per byte.
+    Hex,
+    /// The UTF-8 bytes as unpadded RFC 4648 base32, in `A-Z` and `2-7`. This
+    /// is 20% shorter than [`Hex`](Encoding::Hex).
+    Base32,
+    /// ASCII letters and digits as they are, with every other byte written as
+    /// `_` and two lowercase hex digits, so `Café & Bar` becomes
+    /// `Caf_c3_a9_20_26_20Bar`. This keeps mostly-ASCII names readable.
+    Escape,
+    /// ASCII letters lowercased and digits kept, with every run of other
+    /// characters replaced by a single `_` and leading and trailing runs
+    /// dropped. The result is cut to [`MAX_LABEL_LENGTH`](super::MAX_LABEL_LENGTH).
+    ///
+    /// This loses information: decoding only turns each `_` back into a
+    /// space.
+    Slugify,
+}
+
+/// Why a label could not be decoded with an [`Encoding`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecodeError {
+    /// The label contains a character the encoding never produces.
+    InvalidCharacter { offset: usize, character: char },
+    /// The label ends partway through an encoded byte.
+    Truncated,
+    /// The decoded bytes are not valid UTF-8.
+    InvalidUtf8,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidCharacter { offset, character } => {
+                write!(f, "invalid character {:?} at offset {}", character, offset)
+            }
+            DecodeError::Truncated => write!(f, "label ends partway through an encoded byte"),
+            DecodeError::InvalidUtf8 => write!(f, "decoded label is not valid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+const BASE32_DIGITS: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+impl Encoding {
+    /// Encodes `segment` as a single label.
+    ///
+    /// The result is only a valid label if it is at most
+    /// [`MAX_LABEL_LENGTH`](super::MAX_LABEL_LENGTH) bytes long, which
+    /// [`Ltree::from_segments`] checks.
+    pub fn encode(self, segment: &str) -> String {
+        let encoded = match self {
+            Encoding::Hex => encode_hex(segment.as_bytes()),
+            Encoding::Base32 => encode_base32(segment.as_bytes()),
+            Encoding::Escape => encode_escape(segment),
+            Encoding::Slugify => slugify(segment),
+        };
+        if encoded.is_empty() {
+            "_".to_string()
+        } else {
+            encoded
+        }
+    }
+
+    /// Decodes a label produced by [`encode`](Encoding::encode).
+    pub fn decode(self, label: &str) -> Result<String, DecodeError> {
+        if label == "_" {
+            return Ok(String::new());
+        }
+        let bytes = match self {
+            Encoding::Hex => decode_hex(label)?,
+            Encoding::Base32 => decode_base32(label)?,
+            Encoding::Escape => decode_escape(label)?,
+            Encoding::Slugify => return decode_slug(label),
+        };
+        String::from_utf8(bytes).map_err(|_| DecodeError::InvalidUtf8)
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .flat_map(|&b| {
+            [
+                HEX_DIGITS[usize::from(b >> 4)],
+                HEX_DIGITS[usize::from(b & 0xf)],
+            ]
+        })
+        .map(char::from)
+        .collect()
+}
+
+fn hex_value(offset: usize, c: char) -> Result<u8, DecodeError> {
+    c.to_digit(16)
+        .filter(|_| !c.is_ascii_uppercase())
+        .map(|d| d as u8)
+        .ok_or(DecodeError::InvalidCharacter {
+            offset,
+            character: c,
+        })
+}
+
+fn decode_hex(label: &str) -> Result<Vec<u8>, DecodeError> {
+    let digits = label
+        .char_indices()
+        .map(|(i, c)| hex_value(i, c))
+        .collect::<Result<Vec<_>, _>>()?;
+    if digits.len() % 2 != 0 {
+        return Err(DecodeError::Truncated);
+    }
+    Ok(digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect())
+}
+
+fn encode_base32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for &b in bytes {
+        buffer = buffer << 8 | u16::from(b);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(char::from(
+                BASE32_DIGITS[usize::from(buffer >> bits & 0x1f)],
+            ));
+        }
+    }
+    if bits > 0 {
+        out.push(char::from(
+            BASE32_DIGITS[usize::from(buffer << (5 - bits) & 0x1f)],
+        ));
+    }
+    out
+}
+
+fn decode_base32(label: &str) -> Result<Vec<u8>, DecodeError> {
+    // Unpadded base32 never leaves 1, 3 or 6 characters in its last group.
+    if matches!(label.len() % 8, 1 | 3 | 6) {
+        return Err(DecodeError::Truncated);
+    }
+    let mut out = Vec::with_capacity(label.len() * 5 / 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for (offset, c) in label.char_indices() {
+        let value = BASE32_DIGITS
+            .iter()
+            .position(|&d| char::from(d) == c)
+            .ok_or(DecodeError::InvalidCharacter {
+                offset,
+                character: c,
+            })?;
+        buffer = buffer << 5 | value as u16;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    // The bits left over after the last byte are always zero when encoded,
+    // so a last character setting any of them is not the encoder's.
+    if buffer & ((1 << bits) - 1) != 0 {
+        let offset = label.len() - 1;
+        return Err(DecodeError::InvalidCharacter {
+            offset,
+            character: char::from(label.as_bytes()[offset]),
+        });
+    }
+    Ok(out)
+}
+
+fn encode_escape(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for &b in segment.as_bytes() {
+        if b.is_ascii_alphanumeric() {
+            out.push(char::from(b));
+        } else {
+            out.push('_');
+            out.push(char::from(HEX_DIGITS[usize::from(b >> 4)]));
+            out.push(char::from(HEX_DIGITS[usize::from(b & 0xf)]));
+        }
+    }
+    out
+}
+
+fn decode_escape(label: &str) -> Result<Vec<u8>, DecodeError> {
+    let mut out = Vec::with_capacity(label.len());
+    let mut chars = label.char_indices();
+    while let Some((offset, c)) = chars.next() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c as u8);
+        } else if c == '_' {
+            let (high, low) = match (chars.next(), chars.next()) {
+                (Some(high), Some(low)) => (high, low),
+                _ => return Err(DecodeError::Truncated),
+            };
+            out.push(hex_value(high.0, high.1)? << 4 | hex_value(low.0, low.1)?);
+        } else {
+            return Err(DecodeError::InvalidCharacter {
+                offset,
+                character: c,
+            });
+        }
+    }
+    Ok(out)
+}
+
+fn slugify(segment: &str) -> String {
+    let mut out = String::with_capacity(segment.len());
+    for word in segment
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        if !out.is_empty() {
+            out.push('_');
+        }
+        out.push_str(&word.to_ascii_lowercase());
+    }
+    out.truncate(super::MAX_LABEL_LENGTH);
+    out.trim_end_matches('_').to_string()
+}
+
+fn decode_slug(label: &str) -> Result<String, DecodeError> {
+    match label
+        .char_indices()
+        .find(|&(_, c)| !(c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'))
+    {
+        Some((offset, character)) => Err(DecodeError::InvalidCharacter { offset, character }),
+        None => Ok(label.replace('_', " ")),
+    }
+}
+
+impl Ltree {
+    /// Builds a path with one label for each of `segments`, encoding each
+    /// with `encoding`.
+    ///
+    /// This fails only if an encoded label is longer than
+    /// [`MAX_LABEL_LENGTH`](super::MAX_LABEL_LENGTH) or there are more than
+    /// [`MAX_LEVELS`](super::MAX_LEVELS) segments.
+    ///
+    /// ```
+    /// use diesel_ltree::{Encoding, Ltree};
+    ///
+    /// let path = Ltree::from_segments(["Café & Bar", "Menu"], Encoding::Base32).unwrap();
+    /// assert_eq!(path.0, "INQWNQ5JEATCAQTBOI.JVSW45I");
+    /// assert_eq!(
+    ///     path.decode_segments(Encoding::Base32),
+    ///     Ok(vec!["Café & Bar".to_string(), "Menu".to_string()])
+    /// );
+    /// ```
+    pub fn from_segments<I, S>(segments: I, encoding: Encoding) -> Result<Ltree, LtreeParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let labels = segments
+            .into_iter()
+            .map(|segment| encoding.encode(segment.as_ref()))
+            .collect::<Vec<_>>();
+        Ltree::parse(&labels.join("."))
+    }
+
+    /// Decodes each label of a path built by
+    /// [`from_segments`](Ltree::from_segments) with the same `encoding`.
+    pub fn decode_segments(&self, encoding: Encoding) -> Result<Vec<String>, DecodeError> {
+        self.labels().map(|label| label.decode(encoding)).collect()
+    }
+}
+
+impl Label<'_> {
+    /// Decodes the label with `encoding`, as [`Encoding::decode`] does.
+    pub fn decode(&self, encoding: Encoding) -> Result<String, DecodeError> {
+        encoding.decode(self.as_str())
+    }
+}
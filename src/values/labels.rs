@@ -0,0 +1,221 @@
+use std::fmt;
+use std::iter::FusedIterator;
+use std::str::Split;
+
+use super::{is_label_char, Ltree, LtreeParseError, MAX_LABEL_LENGTH};
+
+/// A single label borrowed from an [`Ltree`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct Label<'a>(&'a str);
+
+impl<'a> Label<'a> {
+    /// Validates `s` as a single label.
+    pub fn new(s: &'a str) -> Result<Label<'a>, LtreeParseError> {
+        if s.is_empty() {
+            return Err(LtreeParseError::EmptyLabel { offset: 0 });
+        }
+        if let Some((offset, character)) = s.char_indices().find(|&(_, c)| !is_label_char(c)) {
+            return Err(LtreeParseError::InvalidCharacter { offset, character });
+        }
+        if s.len() > MAX_LABEL_LENGTH {
+            return Err(LtreeParseError::LabelTooLong {
+                offset: 0,
+                length: s.len(),
+            });
+        }
+        Ok(Label(s))
+    }
+
+    /// Returns the label's text.
+    pub fn as_str(&self) -> &'a str {
+        self.0
+    }
+}
+
+impl AsRef<str> for Label<'_> {
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+impl fmt::Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl PartialEq<str> for Label<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Label<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+/// An iterator over the labels of an [`Ltree`], created by [`Ltree::labels`].
+#[derive(Debug, Clone)]
+pub struct Labels<'a>(Option<Split<'a, char>>);
+
+impl<'a> Iterator for Labels<'a> {
+    type Item = Label<'a>;
+
+    fn next(&mut self) -> Option<Label<'a>> {
+        self.0.as_mut()?.next().map(Label)
+    }
+}
+
+impl<'a> DoubleEndedIterator for Labels<'a> {
+    fn next_back(&mut self) -> Option<Label<'a>> {
+        self.0.as_mut()?.next_back().map(Label)
+    }
+}
+
+impl FusedIterator for Labels<'_> {}
+
+/// An iterator over the non-empty prefixes of an [`Ltree`], shortest first,
+/// created by [`Ltree::iter_prefixes`].
+#[derive(Debug, Clone)]
+pub struct Prefixes<'a> {
+    path: &'a str,
+    // Byte offsets one past the end of the next prefix to yield from the
+    // front and from the back.
+    front: usize,
+    back: usize,
+}
+
+impl<'a> Iterator for Prefixes<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.front > self.back {
+            return None;
+        }
+        let end = self.path[self.front..]
+            .find('.')
+            .map_or(self.path.len(), |i| self.front + i);
+        self.front = end + 1;
+        Some(&self.path[..end])
+    }
+}
+
+impl<'a> DoubleEndedIterator for Prefixes<'a> {
+    fn next_back(&mut self) -> Option<&'a str> {
+        if self.front > self.back {
+            return None;
+        }
+        let end = self.back;
+        match self.path[..end].rfind('.') {
+            Some(i) => self.back = i,
+            None => self.front = self.back + 1,
+        }
+        Some(&self.path[..end])
+    }
+}
+
+impl FusedIterator for Prefixes<'_> {}
+
+/// An iterator over the ancestors of an [`Ltree`], nearest first, created by
+/// [`Ltree::ancestors`].
+#[derive(Debug, Clone)]
+pub struct Ancestors<'a>(Prefixes<'a>);
+
+impl Iterator for Ancestors<'_> {
+    type Item = Ltree;
+
+    fn next(&mut self) -> Option<Ltree> {
+        self.0.next_back().map(|p| Ltree(p.to_string()))
+    }
+}
+
+impl DoubleEndedIterator for Ancestors<'_> {
+    fn next_back(&mut self) -> Option<Ltree> {
+        self.0.next().map(|p| Ltree(p.to_string()))
+    }
+}
+
+impl FusedIterator for Ancestors<'_> {}
+
+impl Ltree {
+    /// Returns the path's text.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns an iterator over the labels of the path.
+    pub fn labels(&self) -> Labels<'_> {
+        Labels((!self.0.is_empty()).then(|| self.0.split('.')))
+    }
+
+    /// Returns the first label of the path, or `None` if the path is empty.
+    pub fn first(&self) -> Option<Label<'_>> {
+        self.labels().next()
+    }
+
+    /// Returns the last label of the path, or `None` if the path is empty.
+    pub fn last(&self) -> Option<Label<'_>> {
+        self.labels().next_back()
+    }
+
+    /// Returns an iterator over every non-empty prefix of the path, from the
+    /// first label up to and including the whole path.
+    pub fn iter_prefixes(&self) -> Prefixes<'_> {
+        let (front, back) = match self.0.is_empty() {
+            true => (1, 0),
+            false => (0, self.0.len()),
+        };
+        Prefixes {
+            path: &self.0,
+            front,
+            back,
+        }
+    }
+
+    /// Returns an iterator over the ancestors of the path, starting with its
+    /// [`parent`](Ltree::parent) and ending with its first label.
+    ///
+    /// Use [`Iterator::rev`] to walk from the top of the tree down, e.g. to
+    /// build breadcrumbs.
+    pub fn ancestors(&self) -> Ancestors<'_> {
+        let mut prefixes = self.iter_prefixes();
+        prefixes.next_back();
+        Ancestors(prefixes)
+    }
+}
+
+impl<'a> IntoIterator for &'a Ltree {
+    type Item = Label<'a>;
+    type IntoIter = Labels<'a>;
+
+    fn into_iter(self) -> Labels<'a> {
+        self.labels()
+    }
+}
+
+impl<'a> Extend<Label<'a>> for Ltree {
+    fn extend<I: IntoIterator<Item = Label<'a>>>(&mut self, iter: I) {
+        for label in iter {
+            if !self.0.is_empty() {
+                self.0.push('.');
+            }
+            self.0.push_str(label.0);
+        }
+    }
+}
+
+impl<'a> FromIterator<Label<'a>> for Ltree {
+    fn from_iter<I: IntoIterator<Item = Label<'a>>>(iter: I) -> Ltree {
+        let mut ltree = Ltree(String::new());
+        ltree.extend(iter);
+        ltree
+    }
+}
+
+impl fmt::Display for Ltree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}